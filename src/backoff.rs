@@ -1,6 +1,133 @@
 // Custom backoff functions for common use cases
+use crate::{default_backoff, BackoffFactory, BackoffFn, Hook};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::any::type_name;
+use std::sync::Arc;
 use std::time::Duration;
 
+/// Per-request backoff state computing the delay before each retry
+///
+/// A fresh state is created by the configured [`BackoffFactory`] for every logical
+/// request, so implementations may remember previous delays or carry their own parameters.
+pub trait Backoff: Send {
+    /// Compute the delay before the given retry attempt (1-based)
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration;
+}
+
+/// Create a factory that hands every request its own clone of `backoff`, labelled with its type
+pub fn factory<B>(backoff: B) -> BackoffFactory
+where
+    B: Backoff + Clone + Sync + 'static,
+{
+    Hook::new(
+        Arc::new(move || Box::new(backoff.clone())),
+        type_name::<B>(),
+    )
+}
+
+/// Adapter running an old-style `fn(attempt, base, multiplier, max)` as a [`Backoff`]
+#[derive(Clone)]
+pub struct FnBackoff(pub BackoffFn);
+
+impl Backoff for FnBackoff {
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        (self.0)(attempt, base_delay, multiplier, max_delay)
+    }
+}
+
+/// Exponential backoff: delay = base_delay * multiplier^attempt (the default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Exponential;
+
+impl Backoff for Exponential {
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        default_backoff(attempt, base_delay, multiplier, max_delay)
+    }
+}
+
+/// Linear backoff strategy, see [`linear`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Linear;
+
+impl Backoff for Linear {
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        linear(attempt, base_delay, multiplier, max_delay)
+    }
+}
+
+/// Fixed delay strategy, see [`fixed`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fixed;
+
+impl Backoff for Fixed {
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        fixed(attempt, base_delay, multiplier, max_delay)
+    }
+}
+
+/// Exponential backoff with jitter, see [`exponential_jitter`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExponentialJitter;
+
+impl Backoff for ExponentialJitter {
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        exponential_jitter(attempt, base_delay, multiplier, max_delay)
+    }
+}
+
+/// Fibonacci backoff strategy, see [`fibonacci`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fibonacci;
+
+impl Backoff for Fibonacci {
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        fibonacci(attempt, base_delay, multiplier, max_delay)
+    }
+}
+
 /// Linear backoff: delay = base_delay * attempt
 pub fn linear(
     attempt: usize,
@@ -35,13 +162,7 @@ pub fn exponential_jitter(
     multiplier: f64,
     max_delay: Duration,
 ) -> Duration {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-
-    // Create pseudo-random jitter based on attempt number
-    let mut hasher = DefaultHasher::new();
-    attempt.hash(&mut hasher);
-    let jitter_factor = (hasher.finish() % 1000) as f64 / 1000.0; // 0.0 to 1.0
+    let jitter_factor = rand::random::<f64>(); // 0.0 to 1.0
 
     let exponential_delay = base_delay.as_millis() as f64 * multiplier.powi(attempt as i32);
     let jittered_delay = exponential_delay * (0.5 + jitter_factor * 0.5); // 50% to 100% of calculated delay
@@ -78,3 +199,141 @@ pub fn fibonacci(
         (base_delay.as_millis() as u64 * fib_multiplier).min(max_delay.as_millis() as u64);
     Duration::from_millis(delay_ms)
 }
+
+/// Random number source for jittered strategies
+///
+/// The generator is created lazily, so every per-request clone of an unseeded
+/// strategy draws fresh OS entropy while seeded strategies replay the same schedule.
+#[derive(Debug, Clone, Default)]
+struct JitterRng {
+    seed: Option<u64>,
+    rng: Option<StdRng>,
+}
+
+impl JitterRng {
+    fn seeded(seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            rng: None,
+        }
+    }
+
+    /// Draw a uniformly distributed fraction in `[0, 1]`
+    fn fraction(&mut self) -> f64 {
+        let seed = self.seed;
+        self.rng
+            .get_or_insert_with(|| match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_os_rng(),
+            })
+            .random_range(0.0..=1.0)
+    }
+}
+
+/// Full jitter: delay = random(0, min(max_delay, base_delay * multiplier^attempt))
+#[derive(Debug, Clone, Default)]
+pub struct FullJitter {
+    rng: JitterRng,
+}
+
+impl FullJitter {
+    /// Create a full jitter strategy drawing from OS entropy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a deterministic generator seeded with `seed`
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: JitterRng::seeded(seed),
+        }
+    }
+}
+
+impl Backoff for FullJitter {
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        let ceiling = default_backoff(attempt, base_delay, multiplier, max_delay);
+        ceiling.mul_f64(self.rng.fraction())
+    }
+}
+
+/// Equal jitter: half of the exponential delay is kept, the other half is randomized
+#[derive(Debug, Clone, Default)]
+pub struct EqualJitter {
+    rng: JitterRng,
+}
+
+impl EqualJitter {
+    /// Create an equal jitter strategy drawing from OS entropy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a deterministic generator seeded with `seed`
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: JitterRng::seeded(seed),
+        }
+    }
+}
+
+impl Backoff for EqualJitter {
+    fn next_delay(
+        &mut self,
+        attempt: usize,
+        base_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        let half = default_backoff(attempt, base_delay, multiplier, max_delay) / 2;
+        half + half.mul_f64(self.rng.fraction())
+    }
+}
+
+/// Decorrelated jitter: delay = min(max_delay, random(base_delay, previous_delay * 3))
+///
+/// The multiplier is ignored; growth comes from the previous delay of the same request.
+#[derive(Debug, Clone, Default)]
+pub struct DecorrelatedJitter {
+    rng: JitterRng,
+    previous: Option<Duration>,
+}
+
+impl DecorrelatedJitter {
+    /// Create a decorrelated jitter strategy drawing from OS entropy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a deterministic generator seeded with `seed`
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: JitterRng::seeded(seed),
+            previous: None,
+        }
+    }
+}
+
+impl Backoff for DecorrelatedJitter {
+    fn next_delay(
+        &mut self,
+        _attempt: usize,
+        base_delay: Duration,
+        _multiplier: f64,
+        max_delay: Duration,
+    ) -> Duration {
+        let upper = self.previous.unwrap_or(base_delay) * 3;
+        let spread = upper
+            .saturating_sub(base_delay)
+            .mul_f64(self.rng.fraction());
+        let delay = (base_delay + spread).min(max_delay);
+        self.previous = Some(delay);
+        delay
+    }
+}
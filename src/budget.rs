@@ -0,0 +1,99 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Token bucket limiting retries across many requests, shared through an `Arc`
+///
+/// Every successful attempt deposits `retry_ratio` tokens and the bucket refills by
+/// `min_retries_per_second` on its own, never exceeding `max_tokens`. Each retry spends
+/// one token; once the bucket is empty, retries are suppressed and the operation fails
+/// with [`RetryError::BudgetExhausted`](crate::RetryError::BudgetExhausted), or
+/// [`RetryError::ResponseBudgetExhausted`](crate::RetryError::ResponseBudgetExhausted)
+/// when the last attempt produced a retryable response.
+#[derive(Debug)]
+pub struct RetryBudget {
+    retry_ratio: f64,
+    min_retries_per_second: f64,
+    max_tokens: f64,
+    inner: Mutex<BudgetInner>,
+}
+
+#[derive(Debug)]
+struct BudgetInner {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Default for RetryBudget {
+    fn default() -> Self {
+        Self {
+            retry_ratio: 0.2,
+            min_retries_per_second: 10.0,
+            max_tokens: 100.0,
+            inner: Mutex::new(BudgetInner {
+                tokens: 100.0,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl RetryBudget {
+    /// Create a retry budget with default values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the retries earned per successful attempt, e.g. `0.2` for 20%
+    pub fn retry_ratio(mut self, ratio: f64) -> Self {
+        self.retry_ratio = ratio.max(0.0);
+        self
+    }
+
+    /// Set the retries allowed per second regardless of recent successes
+    pub fn min_retries_per_second(mut self, rate: f64) -> Self {
+        self.min_retries_per_second = rate.max(0.0);
+        self
+    }
+
+    /// Set the bucket capacity, which is also the initial balance
+    pub fn max_tokens(mut self, max_tokens: f64) -> Self {
+        self.max_tokens = max_tokens.max(0.0);
+        self.inner.get_mut().unwrap().tokens = self.max_tokens;
+        self
+    }
+
+    /// Number of retries currently available
+    pub fn available(&self) -> f64 {
+        let mut inner = self.inner.lock().unwrap();
+        self.refill(&mut inner);
+        inner.tokens
+    }
+
+    /// Record a successful attempt
+    pub(crate) fn deposit(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        self.refill(&mut inner);
+        inner.tokens = (inner.tokens + self.retry_ratio).min(self.max_tokens);
+    }
+
+    /// Spend one token for a retry, returning whether the retry may happen
+    pub(crate) fn try_withdraw(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        self.refill(&mut inner);
+        if inner.tokens >= 1.0 {
+            inner.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Add the tokens earned through the minimum rate since the last refill
+    fn refill(&self, inner: &mut BudgetInner) {
+        let now = Instant::now();
+        let earned =
+            now.duration_since(inner.refilled_at).as_secs_f64() * self.min_retries_per_second;
+        inner.tokens = (inner.tokens + earned).min(self.max_tokens);
+        inner.refilled_at = now;
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally while outcomes are tracked
+    Closed,
+    /// Requests fail fast until the cool-down has elapsed
+    Open,
+    /// A limited number of probe requests decide whether to close again
+    HalfOpen,
+}
+
+/// Circuit breaker shared among many requests through an `Arc`
+///
+/// While closed, the outcome of every attempt within the sliding window is recorded.
+/// Once at least `minimum_requests` outcomes were seen and the failure rate reaches the
+/// threshold, the circuit opens and attempts fail fast with
+/// [`RetryError::CircuitOpen`](crate::RetryError::CircuitOpen). After the cool-down,
+/// `probe_requests` attempts are let through; if they all succeed the circuit closes,
+/// and any failure opens it again.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_rate_threshold: f64,
+    minimum_requests: usize,
+    window: Duration,
+    cool_down: Duration,
+    probe_requests: usize,
+    inner: Mutex<BreakerInner>,
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: CircuitState,
+    /// Timestamped outcomes (`true` for failure) observed while closed
+    outcomes: VecDeque<(Instant, bool)>,
+    /// When the circuit last opened or became half-open
+    changed_at: Instant,
+    probes_in_flight: usize,
+    probe_successes: usize,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_rate_threshold: 0.5,
+            minimum_requests: 10,
+            window: Duration::from_secs(30),
+            cool_down: Duration::from_secs(30),
+            probe_requests: 1,
+            inner: Mutex::new(BreakerInner {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::new(),
+                changed_at: Instant::now(),
+                probes_in_flight: 0,
+                probe_successes: 0,
+            }),
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a circuit breaker with default values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the failure rate (0.0 to 1.0) at which the circuit opens
+    pub fn failure_rate_threshold(mut self, threshold: f64) -> Self {
+        self.failure_rate_threshold = threshold;
+        self
+    }
+
+    /// Set the minimum number of outcomes in the window before the failure rate is evaluated
+    pub fn minimum_requests(mut self, minimum: usize) -> Self {
+        self.minimum_requests = minimum;
+        self
+    }
+
+    /// Set the sliding window over which the failure rate is computed
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set how long the circuit stays open before letting probe requests through
+    pub fn cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Set how many successful probes are needed to close the circuit again
+    pub fn probe_requests(mut self, probes: usize) -> Self {
+        self.probe_requests = probes.max(1);
+        self
+    }
+
+    /// Current state of the circuit
+    pub fn state(&self) -> CircuitState {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+        inner.state
+    }
+
+    /// Ask permission to make an attempt
+    pub(crate) fn try_acquire(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+
+        match inner.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen if inner.probes_in_flight < self.probe_requests => {
+                inner.probes_in_flight += 1;
+                true
+            }
+            CircuitState::HalfOpen => false,
+        }
+    }
+
+    /// Record the outcome of a permitted attempt
+    pub(crate) fn record(&self, failure: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        match inner.state {
+            CircuitState::Closed => {
+                inner.outcomes.push_back((now, failure));
+                while inner
+                    .outcomes
+                    .front()
+                    .is_some_and(|(at, _)| now.duration_since(*at) > self.window)
+                {
+                    inner.outcomes.pop_front();
+                }
+
+                let total = inner.outcomes.len();
+                let failures = inner.outcomes.iter().filter(|(_, failed)| *failed).count();
+                if total >= self.minimum_requests
+                    && failures as f64 / total as f64 >= self.failure_rate_threshold
+                {
+                    Self::transition(&mut inner, CircuitState::Open);
+                }
+            }
+            CircuitState::HalfOpen if failure => Self::transition(&mut inner, CircuitState::Open),
+            CircuitState::HalfOpen => {
+                inner.probe_successes += 1;
+                if inner.probe_successes >= self.probe_requests {
+                    Self::transition(&mut inner, CircuitState::Closed);
+                }
+            }
+            // Attempts admitted before the circuit opened do not change its state
+            CircuitState::Open => {}
+        }
+    }
+
+    /// Apply time-based transitions
+    fn refresh(&self, inner: &mut BreakerInner) {
+        let elapsed = inner.changed_at.elapsed();
+        match inner.state {
+            CircuitState::Open if elapsed >= self.cool_down => {
+                Self::transition(inner, CircuitState::HalfOpen);
+            }
+            // Probes that never reported back (e.g. dropped futures) must not wedge the circuit
+            CircuitState::HalfOpen if elapsed >= self.cool_down => {
+                Self::transition(inner, CircuitState::HalfOpen);
+            }
+            _ => {}
+        }
+    }
+
+    fn transition(inner: &mut BreakerInner, state: CircuitState) {
+        inner.state = state;
+        inner.outcomes.clear();
+        inner.changed_at = Instant::now();
+        inner.probes_in_flight = 0;
+        inner.probe_successes = 0;
+    }
+}
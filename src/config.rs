@@ -1,13 +1,25 @@
+use crate::backoff::{self, Backoff};
+use crate::budget::RetryBudget;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::failover::Failover;
 use crate::{
-    default_backoff, default_error_classifier, default_response_classifier, default_should_retry_error, default_should_retry_response,
-    EffectiveStrategy, ErrorStrategy, RetryAttempt,
-    RetryReason,
+    default_error_classifier, default_response_classifier, default_should_retry_error,
+    default_should_retry_response, BackoffFactory, EffectiveStrategy, ErrorClassifier, Hook,
+    BeforeRetryHook, ErrorPredicate, ErrorStrategy, ResponseClassifier, ResponsePredicate,
+    RetryAttempt, IdempotencyKeyFn, RetryCallback, RetryReason, IDEMPOTENCY_KEY,
 };
-use reqwest::{Error as ReqwestError, Response};
-use std::collections::HashMap;
-use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Error as ReqwestError, Method, Request, Response, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::any::{type_name, type_name_of_val};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Configuration for retry behavior
+///
+/// Cloning is cheap: hooks and shared state such as the circuit breaker are kept in `Arc`s.
+#[derive(Clone)]
 pub struct RetryConfig {
     /// Maximum number of retry attempts (default fallback)
     pub max_retries: usize,
@@ -18,22 +30,88 @@ pub struct RetryConfig {
     /// Multiplier for exponential backoff (default fallback)
     pub backoff_multiplier: f64,
     /// Function to determine if an error should trigger a retry
-    pub should_retry: fn(&ReqwestError) -> bool,
+    pub should_retry: ErrorPredicate,
     /// Function to determine if a response should trigger a retry
-    pub should_retry_response: fn(&Response) -> bool,
-    /// Custom backoff calculation function (default fallback)
-    pub backoff_fn:
-        fn(attempt: usize, base_delay: Duration, multiplier: f64, max_delay: Duration) -> Duration,
+    pub should_retry_response: ResponsePredicate,
+    /// Factory for the per-request backoff strategy (default fallback)
+    pub backoff: BackoffFactory,
     /// Callback called before each retry attempt
-    pub on_retry: Option<fn(&RetryAttempt)>,
+    pub on_retry: Option<RetryCallback>,
     /// Callback called when retries are exhausted
-    pub on_failure: Option<fn(&RetryAttempt)>,
+    pub on_failure: Option<RetryCallback>,
+    /// Async hook awaited after the delay, before the next attempt is sent
+    pub before_retry: Option<BeforeRetryHook>,
     /// Error-specific retry strategies
     pub error_strategies: HashMap<RetryReason, ErrorStrategy>,
+    /// Status-specific retry strategies, taking precedence over error-specific ones
+    pub status_strategies: HashMap<StatusCode, ErrorStrategy>,
     /// Function to classify errors into retry reasons
-    pub error_classifier: fn(&ReqwestError) -> RetryReason,
+    pub error_classifier: ErrorClassifier,
     /// Function to classify response statuses into retry reasons
-    pub response_classifier: fn(&Response) -> RetryReason,
+    pub response_classifier: ResponseClassifier,
+    /// Whether to wait for the delay given by `Retry-After` on 429 and 503 responses
+    pub respect_retry_after: bool,
+    /// Longest `Retry-After` delay we will accept; a longer one stops retrying
+    pub max_retry_after: Duration,
+    /// Whether a still-retryable response is returned as an error once retries are exhausted
+    pub error_on_exhausted_response: bool,
+    /// Maximum time a single attempt may take
+    pub attempt_timeout: Option<Duration>,
+    /// Maximum time the whole operation may take, including delays between attempts
+    pub deadline: Option<Duration>,
+    /// HTTP methods that may be retried (idempotent methods by default)
+    pub retryable_methods: HashSet<Method>,
+    /// Whether requests of any method are retried when they carry an `Idempotency-Key` header
+    pub retry_with_idempotency_key: bool,
+    /// Generator for an `Idempotency-Key` added to requests that lack one
+    pub idempotency_key: Option<IdempotencyKeyFn>,
+    /// Circuit breaker shared with other requests
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Retry budget shared with other requests
+    pub retry_budget: Option<Arc<RetryBudget>>,
+    /// Endpoints that attempts fail over across
+    pub failover: Option<Arc<Failover>>,
+    /// Maximum size of a streaming body buffered so that it can be replayed
+    pub body_buffer_limit: Option<usize>,
+}
+
+impl fmt::Debug for RetryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryConfig")
+            .field("max_retries", &self.max_retries)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("backoff_multiplier", &self.backoff_multiplier)
+            .field("should_retry", &self.should_retry)
+            .field("should_retry_response", &self.should_retry_response)
+            .field("backoff", &self.backoff)
+            .field("on_retry", &self.on_retry)
+            .field("on_failure", &self.on_failure)
+            .field("before_retry", &self.before_retry)
+            .field("error_strategies", &self.error_strategies)
+            .field("status_strategies", &self.status_strategies)
+            .field("error_classifier", &self.error_classifier)
+            .field("response_classifier", &self.response_classifier)
+            .field("respect_retry_after", &self.respect_retry_after)
+            .field("max_retry_after", &self.max_retry_after)
+            .field(
+                "error_on_exhausted_response",
+                &self.error_on_exhausted_response,
+            )
+            .field("attempt_timeout", &self.attempt_timeout)
+            .field("deadline", &self.deadline)
+            .field("retryable_methods", &self.retryable_methods)
+            .field(
+                "retry_with_idempotency_key",
+                &self.retry_with_idempotency_key,
+            )
+            .field("idempotency_key", &self.idempotency_key)
+            .field("circuit_breaker", &self.circuit_breaker)
+            .field("retry_budget", &self.retry_budget)
+            .field("failover", &self.failover)
+            .field("body_buffer_limit", &self.body_buffer_limit)
+            .finish()
+    }
 }
 
 impl Default for RetryConfig {
@@ -43,14 +121,35 @@ impl Default for RetryConfig {
             base_delay: Duration::from_millis(100),
             max_delay: Duration::from_secs(30),
             backoff_multiplier: 2.0,
-            should_retry: default_should_retry_error,
-            should_retry_response: default_should_retry_response,
-            backoff_fn: default_backoff,
+            should_retry: Hook::new(Arc::new(default_should_retry_error), "default"),
+            should_retry_response: Hook::new(Arc::new(default_should_retry_response), "default"),
+            backoff: backoff::factory(backoff::Exponential),
             on_retry: None,
             on_failure: None,
+            before_retry: None,
             error_strategies: HashMap::new(),
-            error_classifier: default_error_classifier,
-            response_classifier: default_response_classifier,
+            status_strategies: HashMap::new(),
+            error_classifier: Hook::new(Arc::new(default_error_classifier), "default"),
+            response_classifier: Hook::new(Arc::new(default_response_classifier), "default"),
+            respect_retry_after: true,
+            max_retry_after: Duration::from_secs(60),
+            error_on_exhausted_response: false,
+            attempt_timeout: None,
+            deadline: None,
+            retryable_methods: HashSet::from([
+                Method::GET,
+                Method::HEAD,
+                Method::OPTIONS,
+                Method::TRACE,
+                Method::PUT,
+                Method::DELETE,
+            ]),
+            retry_with_idempotency_key: false,
+            idempotency_key: None,
+            circuit_breaker: None,
+            retry_budget: None,
+            failover: None,
+            body_buffer_limit: None,
         }
     }
 }
@@ -86,35 +185,84 @@ impl RetryConfig {
     }
 
     /// Set custom error retry predicate
-    pub fn should_retry_error(mut self, predicate: fn(&ReqwestError) -> bool) -> Self {
-        self.should_retry = predicate;
+    pub fn should_retry_error(
+        mut self,
+        predicate: impl Fn(&ReqwestError) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let label = type_name_of_val(&predicate);
+        self.should_retry = Hook::new(Arc::new(predicate), label);
         self
     }
 
     /// Set custom response retry predicate
-    pub fn should_retry_response(mut self, predicate: fn(&Response) -> bool) -> Self {
-        self.should_retry_response = predicate;
+    pub fn should_retry_response(
+        mut self,
+        predicate: impl Fn(&Response) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let label = type_name_of_val(&predicate);
+        self.should_retry_response = Hook::new(Arc::new(predicate), label);
+        self
+    }
+
+    /// Set backoff strategy, cloned into a fresh state for every request
+    pub fn backoff<B>(mut self, backoff: B) -> Self
+    where
+        B: Backoff + Clone + Sync + 'static,
+    {
+        self.backoff = backoff::factory(backoff);
+        self
+    }
+
+    /// Set custom factory creating the backoff state for every request
+    pub fn backoff_factory(
+        mut self,
+        factory: impl Fn() -> Box<dyn Backoff> + Send + Sync + 'static,
+    ) -> Self {
+        let label = type_name_of_val(&factory);
+        self.backoff = Hook::new(Arc::new(factory), label);
         self
     }
 
     /// Set custom backoff calculation function
     pub fn backoff_fn(
         mut self,
-        backoff_fn: fn(usize, Duration, f64, Duration) -> Duration,
+        backoff_fn: impl Fn(usize, Duration, f64, Duration) -> Duration + Send + Sync + 'static,
     ) -> Self {
-        self.backoff_fn = backoff_fn;
+        let label = type_name_of_val(&backoff_fn);
+        self.backoff = backoff::factory(backoff::FnBackoff(Arc::new(backoff_fn))).with_label(label);
         self
     }
 
     /// Set callback for retry attempts
-    pub fn on_retry(mut self, callback: fn(&RetryAttempt)) -> Self {
-        self.on_retry = Some(callback);
+    pub fn on_retry(mut self, callback: impl Fn(&RetryAttempt) + Send + Sync + 'static) -> Self {
+        let label = type_name_of_val(&callback);
+        self.on_retry = Some(Hook::new(Arc::new(callback), label));
         self
     }
 
     /// Set callback for when retries are exhausted
-    pub fn on_failure(mut self, callback: fn(&RetryAttempt)) -> Self {
-        self.on_failure = Some(callback);
+    pub fn on_failure(mut self, callback: impl Fn(&RetryAttempt) + Send + Sync + 'static) -> Self {
+        let label = type_name_of_val(&callback);
+        self.on_failure = Some(Hook::new(Arc::new(callback), label));
+        self
+    }
+
+    /// Set an async hook awaited before every retry, e.g. to refresh an expired token
+    ///
+    /// The hook receives the retry details and the request for the next attempt. It returns
+    /// the (possibly modified) request to proceed, or `None` to stop with
+    /// [`RetryError::RetryCancelled`](crate::RetryError::RetryCancelled). Changes are kept
+    /// for all later attempts.
+    pub fn before_retry<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(RetryAttempt, Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<Request>> + Send + 'static,
+    {
+        let label = type_name::<F>();
+        self.before_retry = Some(Hook::new(
+            Arc::new(move |attempt, request| Box::pin(hook(attempt, request)) as _),
+            label,
+        ));
         self
     }
 
@@ -124,21 +272,186 @@ impl RetryConfig {
         self
     }
 
+    /// Set retry strategy for responses with a specific status, e.g. a 503 apart from a 500
+    pub fn status_strategy(mut self, status: StatusCode, strategy: ErrorStrategy) -> Self {
+        self.status_strategies.insert(status, strategy);
+        self
+    }
+
     /// Set custom error classifier
-    pub fn error_classifier(mut self, classifier: fn(&ReqwestError) -> RetryReason) -> Self {
-        self.error_classifier = classifier;
+    pub fn error_classifier(
+        mut self,
+        classifier: impl Fn(&ReqwestError) -> RetryReason + Send + Sync + 'static,
+    ) -> Self {
+        let label = type_name_of_val(&classifier);
+        self.error_classifier = Hook::new(Arc::new(classifier), label);
         self
     }
 
     /// Set custom response classifier
-    pub fn response_classifier(mut self, classifier: fn(&Response) -> RetryReason) -> Self {
-        self.response_classifier = classifier;
+    pub fn response_classifier(
+        mut self,
+        classifier: impl Fn(&Response) -> RetryReason + Send + Sync + 'static,
+    ) -> Self {
+        let label = type_name_of_val(&classifier);
+        self.response_classifier = Hook::new(Arc::new(classifier), label);
+        self
+    }
+
+    /// Set whether `Retry-After` headers on 429 and 503 responses are honored
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+
+    /// Set the longest `Retry-After` delay we will accept
+    ///
+    /// When a server asks to wait longer, retrying stops instead of coming back early.
+    pub fn max_retry_after(mut self, ceiling: Duration) -> Self {
+        self.max_retry_after = ceiling;
+        self
+    }
+
+    /// Set whether exhausting retries on a retryable response yields
+    /// [`RetryError::ResponseRetriesExhausted`](crate::RetryError::ResponseRetriesExhausted)
+    /// instead of `Ok(response)`
+    ///
+    /// Requests that read the response body always report exhausted responses this way.
+    pub fn error_on_exhausted_response(mut self, enabled: bool) -> Self {
+        self.error_on_exhausted_response = enabled;
+        self
+    }
+
+    /// Set the maximum time a single attempt may take
+    ///
+    /// A shorter timeout set on the request itself is kept.
+    pub fn attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum time the whole operation may take
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Allow retrying requests with the given method, e.g. `POST` to an idempotent endpoint
+    pub fn allow_method(mut self, method: Method) -> Self {
+        self.retryable_methods.insert(method);
+        self
+    }
+
+    /// Set the HTTP methods that may be retried
+    pub fn retryable_methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.retryable_methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Set whether requests carrying an `Idempotency-Key` header are retried regardless of method
+    pub fn retry_with_idempotency_key(mut self, enabled: bool) -> Self {
+        self.retry_with_idempotency_key = enabled;
+        self
+    }
+
+    /// Add a random UUIDv4 `Idempotency-Key` to requests that lack one
+    ///
+    /// Requests carrying a key become retryable regardless of their method.
+    pub fn generate_idempotency_key(self) -> Self {
+        self.idempotency_key_generator(|| uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Add an `Idempotency-Key` produced by `generator` to requests that lack one
+    ///
+    /// The generator runs once per logical request and the key is reused on every attempt.
+    /// Keys that are not valid header values are not added.
+    pub fn idempotency_key_generator(
+        mut self,
+        generator: impl Fn() -> String + Send + Sync + 'static,
+    ) -> Self {
+        let label = type_name_of_val(&generator);
+        self.idempotency_key = Some(Hook::new(Arc::new(generator), label));
+        self
+    }
+
+    /// Set a circuit breaker, typically shared with other requests to the same dependency
+    pub fn circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(breaker);
         self
     }
 
-    /// Get effective strategy for a specific error type
-    pub(crate) fn get_effective_strategy(&self, error_type: &RetryReason) -> EffectiveStrategy {
-        let strategy = self.error_strategies.get(error_type);
+    /// Set a retry budget, typically shared with other requests to the same dependency
+    pub fn retry_budget(mut self, budget: Arc<RetryBudget>) -> Self {
+        self.retry_budget = Some(budget);
+        self
+    }
+
+    /// Spread attempts over equivalent endpoints, typically shared with other requests
+    pub fn failover(mut self, failover: Arc<Failover>) -> Self {
+        self.failover = Some(failover);
+        self
+    }
+
+    /// Buffer streaming bodies up to `limit` bytes while they are sent, so they can be replayed
+    ///
+    /// Once a body grows beyond the limit, retrying stops and the outcome of the attempt
+    /// that sent it is returned as if retries were exhausted.
+    pub fn buffer_body(mut self, limit: usize) -> Self {
+        self.body_buffer_limit = Some(limit);
+        self
+    }
+
+    /// Apply per-request preparation shared by all attempts, before the first one is made
+    pub(crate) fn prepare_request(&self, request: &mut Request) {
+        if let Some(generator) = &self.idempotency_key
+            && !request.headers().contains_key(IDEMPOTENCY_KEY)
+            && let Ok(key) = HeaderValue::from_str(&generator())
+        {
+            request.headers_mut().insert(IDEMPOTENCY_KEY, key);
+        }
+    }
+
+    /// Check whether a request with this method and headers may be retried
+    pub(crate) fn is_retry_allowed(&self, method: &Method, headers: &HeaderMap) -> bool {
+        let keyed = self.retry_with_idempotency_key || self.idempotency_key.is_some();
+        self.retryable_methods.contains(method) || (keyed && headers.contains_key(IDEMPOTENCY_KEY))
+    }
+
+    /// Check whether sleeping for `delay` would overshoot the overall deadline
+    pub(crate) fn exceeds_deadline(&self, started: Option<Instant>, delay: Duration) -> bool {
+        match (self.deadline, started) {
+            (Some(deadline), Some(started)) => started
+                .elapsed()
+                .checked_add(delay)
+                .is_none_or(|elapsed| elapsed >= deadline),
+            _ => false,
+        }
+    }
+
+    /// Spend from the retry budget, returning whether a retry may happen
+    pub(crate) fn try_withdraw_retry(&self) -> bool {
+        self.retry_budget
+            .as_ref()
+            .is_none_or(|budget| budget.try_withdraw())
+    }
+
+    /// Get effective strategy for a specific error type, preferring the one set for `status`
+    ///
+    /// Without a strategy for the status or the error type itself, the strategy of its
+    /// [broader](RetryReason::broader) reason applies.
+    pub(crate) fn get_status_strategy(
+        &self,
+        error_type: &RetryReason,
+        status: Option<StatusCode>,
+    ) -> EffectiveStrategy {
+        let strategy = status
+            .and_then(|status| self.status_strategies.get(&status))
+            .or_else(|| self.error_strategies.get(error_type))
+            .or_else(|| {
+                error_type
+                    .broader()
+                    .and_then(|broader| self.error_strategies.get(&broader))
+            });
 
         EffectiveStrategy {
             max_retries: strategy
@@ -151,9 +464,9 @@ impl RetryConfig {
             backoff_multiplier: strategy
                 .and_then(|s| s.backoff_multiplier)
                 .unwrap_or(self.backoff_multiplier),
-            backoff_fn: strategy
-                .and_then(|s| s.backoff_fn)
-                .unwrap_or(self.backoff_fn),
+            backoff: strategy
+                .and_then(|s| s.backoff.clone())
+                .unwrap_or_else(|| self.backoff.clone()),
         }
     }
 }
@@ -0,0 +1,92 @@
+use crate::config::RetryConfig;
+use crate::drive::{drive, Attempt};
+use crate::error::RetryError;
+use crate::tracker::RetryTracker;
+use reqwest::{Error as ReqwestError, RequestBuilder, Response};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Future that retries a request together with reading its response body
+///
+/// A successful response only completes an attempt once its body was read and decoded;
+/// body and decode failures are retried like errors of the request itself.
+pub struct DecodeFuture<T> {
+    future: Pin<Box<dyn Future<Output = Result<T, RetryError>> + Send>>,
+}
+
+/// Value read within an attempt, or a final response whose body is still unread
+enum Decoded<T> {
+    Value(T),
+    Response(Response),
+}
+
+impl<T> From<Response> for Decoded<T> {
+    fn from(response: Response) -> Self {
+        Decoded::Response(response)
+    }
+}
+
+impl<T: Send + 'static> DecodeFuture<T> {
+    pub(crate) fn new<F, Fut>(
+        request_builder: RequestBuilder,
+        config: Arc<RetryConfig>,
+        read: F,
+    ) -> Self
+    where
+        F: Fn(Response) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, ReqwestError>> + Send,
+    {
+        let (client, request) = request_builder.build_split();
+
+        Self {
+            future: Box::pin(async move {
+                // A request that failed to build can never succeed
+                let mut req = request.map_err(RetryError::NonRetryableError)?;
+
+                // A response still failing once retries run out has no body worth decoding
+                let tracker =
+                    RetryTracker::start(&config, Some(&mut req)).error_on_exhausted_response();
+
+                let (client, config, read) = (&client, &config, &read);
+                let decoded = drive(tracker, config, req, |request| async move {
+                    let attempt = match client.execute(request).await {
+                        // Read the body of a successful, final response as part of the attempt
+                        Ok(response)
+                            if response.status().is_success()
+                                && !(config.should_retry_response)(&response) =>
+                        {
+                            match read(response).await {
+                                Ok(value) => Attempt::Value(Decoded::Value(value)),
+                                Err(error) => Attempt::Error(error),
+                            }
+                        }
+                        Ok(response) => Attempt::Response(response),
+                        Err(error) => Attempt::Error(error),
+                    };
+                    Ok::<_, RetryError>(attempt)
+                })
+                .await?;
+
+                match decoded {
+                    Decoded::Value(value) => Ok(value),
+                    Decoded::Response(response) => {
+                        // Other final responses are errors unless their status is a success
+                        let response = response
+                            .error_for_status()
+                            .map_err(RetryError::NonRetryableError)?;
+                        read(response).await.map_err(RetryError::NonRetryableError)
+                    }
+                }
+            }),
+        }
+    }
+}
+
+impl<T> Future for DecodeFuture<T> {
+    type Output = Result<T, RetryError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.future.as_mut().poll(cx)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::config::RetryConfig;
+use crate::error::RetryError;
+use crate::tracker::{Decision, RetryTracker};
+use reqwest::{Error as ReqwestError, Request, Response};
+
+/// Outcome of one attempt made by the `send` function of [`drive`]
+pub(crate) enum Attempt<T> {
+    /// The server answered; the response is classified and returned once it is final
+    Response(Response),
+    /// The request failed with an error to classify
+    Error(ReqwestError),
+    /// The attempt completed with its final value, e.g. a decoded body
+    Value(T),
+}
+
+/// Make attempts at `req` through `send` until `tracker` stops retrying
+///
+/// Each attempt gets a fresh copy of the request from [`RetryTracker::next_request`].
+/// Between attempts the delay is slept and the `before_retry` hook is run. Errors
+/// returned by `send` end the request without a retry.
+pub(crate) async fn drive<T, E, F, Fut>(
+    mut tracker: RetryTracker,
+    config: &RetryConfig,
+    mut req: Request,
+    mut send: F,
+) -> Result<T, E>
+where
+    T: From<Response>,
+    E: From<RetryError>,
+    F: FnMut(Request) -> Fut,
+    Fut: Future<Output = Result<Attempt<T>, E>>,
+{
+    loop {
+        let request = tracker.next_request(config, &req)?;
+        let decision = match tracker.instrument(send(request)).await? {
+            Attempt::Response(response) => tracker.on_response(config, response),
+            Attempt::Error(error) => tracker.on_error(config, error),
+            Attempt::Value(value) => {
+                tracker.on_success(config);
+                return Ok(value);
+            }
+        };
+
+        match decision {
+            Decision::Retry(delay) => tokio::time::sleep(delay).await,
+            Decision::Done(result) => return Ok(T::from(result?)),
+        }
+
+        // Let the hook adjust the request before the next attempt
+        if let (Some(hook), Some(attempt)) = (&config.before_retry, tracker.last_retry()) {
+            match hook(attempt.clone(), req).await {
+                Some(request) => req = request,
+                None => return Err(tracker.cancel().into()),
+            }
+        }
+    }
+}
@@ -1,17 +1,76 @@
-use reqwest::Error as ReqwestError;
+use crate::history::RetryHistory;
+use reqwest::{Error as ReqwestError, Response};
 use thiserror::Error;
 
 /// Errors that can occur during retry operations
 #[derive(Error, Debug)]
 pub enum RetryError {
-    #[error("Maximum retry attempts exceeded")]
-    MaxRetriesExceeded,
+    #[error("Maximum retry attempts exceeded after {} attempts", .history.attempts.len())]
+    MaxRetriesExceeded { history: RetryHistory },
     #[error("Request failed with non-retryable error: {0}")]
     NonRetryableError(ReqwestError),
-    #[error("Request failed: {0}")]
-    RequestError(ReqwestError),
+    #[error("Request failed after {} attempts: {source}", .history.attempts.len())]
+    RequestError {
+        source: ReqwestError,
+        history: RetryHistory,
+    },
+    #[error(
+        "Response status {} still retryable after {} attempts",
+        .response.status(),
+        .history.attempts.len()
+    )]
+    ResponseRetriesExhausted {
+        response: Response,
+        history: RetryHistory,
+    },
+    #[error("Retry deadline exceeded after {} attempts", .history.attempts.len())]
+    DeadlineExceeded { history: RetryHistory },
+    #[error("Circuit breaker is open after {} attempts", .history.attempts.len())]
+    CircuitOpen { history: RetryHistory },
+    #[error("Retry budget exhausted after {} attempts: {source}", .history.attempts.len())]
+    BudgetExhausted {
+        source: ReqwestError,
+        history: RetryHistory,
+    },
+    #[error(
+        "Retry budget exhausted after {} attempts: response status {}",
+        .history.attempts.len(),
+        .response.status()
+    )]
+    ResponseBudgetExhausted {
+        response: Response,
+        history: RetryHistory,
+    },
+    #[error("Retrying was cancelled by the before_retry hook after {} attempts", .history.attempts.len())]
+    RetryCancelled { history: RetryHistory },
     #[error("Cannot clone request builder - request body may not be cloneable")]
     RequestBuilderCloneError,
     #[error("Request builder not available")]
     RequestBuilderNotAvailable,
 }
+
+impl RetryError {
+    /// Attempt history, if retries were exhausted
+    pub fn history(&self) -> Option<&RetryHistory> {
+        match self {
+            RetryError::MaxRetriesExceeded { history }
+            | RetryError::RequestError { history, .. }
+            | RetryError::ResponseRetriesExhausted { history, .. }
+            | RetryError::DeadlineExceeded { history }
+            | RetryError::CircuitOpen { history }
+            | RetryError::BudgetExhausted { history, .. }
+            | RetryError::ResponseBudgetExhausted { history, .. }
+            | RetryError::RetryCancelled { history } => Some(history),
+            _ => None,
+        }
+    }
+
+    /// Take the final response, if retrying stopped on a retryable response
+    pub fn into_response(self) -> Option<Response> {
+        match self {
+            RetryError::ResponseRetriesExhausted { response, .. }
+            | RetryError::ResponseBudgetExhausted { response, .. } => Some(response),
+            _ => None,
+        }
+    }
+}
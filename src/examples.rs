@@ -23,6 +23,8 @@ async fn advanced_usage_with_logging() -> Result<reqwest::Response, RetryError>
         .should_retry_error(predicates::network_errors_only)
         .should_retry_response(predicates::server_errors_and_rate_limit)
         .backoff_fn(backoff::exponential_jitter)
+        // POST is not idempotent; only retry it because this endpoint is known to be safe
+        .allow_method(reqwest::Method::POST)
         .on_retry(|attempt| {
             println!(
                 "Retrying request (attempt {}/{}) after {}ms delay. Reason: {}",
@@ -66,12 +68,23 @@ async fn different_backoff_strategies() -> Result<reqwest::Response, RetryError>
     // Fibonacci backoff
     let fibonacci_config = RetryConfig::new().backoff_fn(backoff::fibonacci);
 
-    // Exponential with jitter (recommended for production)
+    // Strategy objects get a fresh state for every request
+    let stateful_config = RetryConfig::new().backoff(backoff::Fibonacci);
+
+    // Exponential with jitter
     let jitter_config = RetryConfig::new().backoff_fn(backoff::exponential_jitter);
 
+    // Randomized jitter strategies (full jitter is recommended for production)
+    let full_jitter_config = RetryConfig::new().backoff(backoff::FullJitter::new());
+    let equal_jitter_config = RetryConfig::new().backoff(backoff::EqualJitter::new());
+    let decorrelated_config = RetryConfig::new().backoff(backoff::DecorrelatedJitter::new());
+
+    // Seeded generators replay the exact same schedule, which is handy in tests
+    let seeded_config = RetryConfig::new().backoff(backoff::FullJitter::seeded(42));
+
     let response = Client::new()
         .get("https://api.example.com/data")
-        .or_retry_with(jitter_config)
+        .or_retry_with(full_jitter_config)
         .await?;
 
     Ok(response)
@@ -88,10 +101,13 @@ async fn post_with_retry() -> Result<reqwest::Response, RetryError> {
         .post("http://localhost:8080/api/uploads/init")
         .json(&request_body)
         .header("Authorization", "Bearer token123")
+        // The idempotency key tells the server to deduplicate repeated uploads
+        .header(IDEMPOTENCY_KEY, "upload-7f3a")
         .or_retry_with(
             RetryConfig::new()
                 .max_retries(3)
-                .base_delay(Duration::from_millis(200)),
+                .base_delay(Duration::from_millis(200))
+                .retry_with_idempotency_key(true),
         )
         .await?;
 
@@ -126,6 +142,7 @@ async fn error_specific_strategies() -> Result<reqwest::Response, RetryError> {
                 .base_delay(Duration::from_millis(500))
                 .backoff_fn(backoff::fibonacci),
         )
+        .allow_method(reqwest::Method::POST)
         .on_retry(|attempt| {
             println!(
                 "Retrying {:?} (attempt {}/{}) after {}ms",
@@ -145,6 +162,35 @@ async fn error_specific_strategies() -> Result<reqwest::Response, RetryError> {
     Ok(response)
 }
 
+/// Usage with closures capturing shared state
+async fn capturing_callbacks() -> Result<reqwest::Response, RetryError> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let retries = Arc::new(AtomicUsize::new(0));
+    let counter = retries.clone();
+
+    let config = RetryConfig::new()
+        .should_retry_response(predicates::retry_on_status(&[
+            reqwest::StatusCode::BAD_GATEWAY,
+            reqwest::StatusCode::SERVICE_UNAVAILABLE,
+        ]))
+        .on_retry(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+    let response = Client::new()
+        .get("https://api.example.com/data")
+        .or_retry_with(config)
+        .await?;
+
+    println!(
+        "Request succeeded after {} retries",
+        retries.load(Ordering::Relaxed)
+    );
+    Ok(response)
+}
+
 /// Usage with custom error classification
 async fn custom_error_classification() -> Result<reqwest::Response, RetryError> {
     // Custom error classifier that recognizes specific API errors
@@ -181,3 +227,333 @@ async fn custom_error_classification() -> Result<reqwest::Response, RetryError>
 
     Ok(response)
 }
+
+/// Usage of the attempt history carried by exhaustion errors
+async fn inspect_history() {
+    let result = Client::new()
+        .get("https://api.example.com/data")
+        .or_retry()
+        .await;
+
+    if let Err(error) = result {
+        if let Some(history) = error.history() {
+            eprintln!("Gave up after {:?}", history.total_elapsed);
+            for record in &history.attempts {
+                eprintln!(
+                    "#{} {:?} ({:?}) took {:?}, slept {:?}",
+                    record.attempt, record.outcome, record.reason, record.elapsed, record.delay
+                );
+            }
+        }
+    }
+}
+
+/// Usage with a per-attempt timeout and an overall deadline
+async fn timeouts_and_deadline() -> Result<reqwest::Response, RetryError> {
+    let config = RetryConfig::new()
+        .max_retries(10)
+        // Each attempt may take at most 2s...
+        .attempt_timeout(Duration::from_secs(2))
+        // ...but the whole operation must finish within 10s
+        .deadline(Duration::from_secs(10));
+
+    match Client::new()
+        .get("https://api.example.com/data")
+        .or_retry_with(config)
+        .await
+    {
+        Err(RetryError::DeadlineExceeded { history }) => {
+            eprintln!("Gave up after {} attempts", history.attempts.len());
+            Err(RetryError::DeadlineExceeded { history })
+        }
+        result => result,
+    }
+}
+
+/// Usage as a `reqwest-middleware` middleware (requires the `middleware` feature)
+#[cfg(feature = "middleware")]
+async fn middleware_stack() -> reqwest_middleware::Result<reqwest::Response> {
+    let client = reqwest_middleware::ClientBuilder::new(Client::new())
+        .with(RetryMiddleware::new(
+            RetryConfig::new()
+                .max_retries(5)
+                .backoff(backoff::FullJitter::new()),
+        ))
+        .build();
+
+    client.get("https://api.example.com/data").send().await
+}
+
+/// Usage as a tower layer around `reqwest::Client` (requires the `tower` feature)
+#[cfg(feature = "tower")]
+async fn tower_stack() -> Result<reqwest::Response, BoxError> {
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    let mut service = RetryLayer::new(RetryConfig::new().max_retries(5)).layer(Client::new());
+
+    let request = Client::new().get("https://api.example.com/data").build()?;
+    std::future::poll_fn(|cx| service.poll_ready(cx)).await?;
+    service.call(request).await
+}
+
+/// Usage with a generated `Idempotency-Key` making POST retries safe
+async fn generated_idempotency_key() -> Result<reqwest::Response, RetryError> {
+    let config = RetryConfig::new()
+        .max_retries(5)
+        // The same UUIDv4 key is sent on every attempt of this payment
+        .generate_idempotency_key();
+
+    Client::new()
+        .post("https://api.example.com/v1/charges")
+        .form(&[("amount", "2000"), ("currency", "usd")])
+        .or_retry_with(config)
+        .await
+}
+
+/// Usage with a circuit breaker shared by all requests to one dependency
+async fn shared_circuit_breaker(
+    breaker: std::sync::Arc<CircuitBreaker>,
+) -> Result<reqwest::Response, RetryError> {
+    // Create the breaker once, e.g. at startup:
+    // Arc::new(CircuitBreaker::new().failure_rate_threshold(0.5).cool_down(Duration::from_secs(10)))
+    let result = Client::new()
+        .get("https://api.example.com/data")
+        .or_retry_with(RetryConfig::new().circuit_breaker(breaker))
+        .await;
+
+    if let Err(RetryError::CircuitOpen { .. }) = &result {
+        eprintln!("Dependency is down, failing fast");
+    }
+    result
+}
+
+/// Usage with a retry budget capping retries across all requests to one dependency
+async fn shared_retry_budget(
+    budget: std::sync::Arc<RetryBudget>,
+) -> Result<reqwest::Response, RetryError> {
+    // Create the budget once, e.g. at startup: retries may add 10% load on top of
+    // successful traffic, plus 5 retries per second regardless
+    // Arc::new(RetryBudget::new().retry_ratio(0.1).min_retries_per_second(5.0))
+    Client::new()
+        .get("https://api.example.com/data")
+        .or_retry_with(
+            RetryConfig::new()
+                .retry_budget(budget)
+                .on_failure(|attempt| {
+                    if attempt.budget_exhausted {
+                        eprintln!("Retry budget spent, not retrying");
+                    }
+                }),
+        )
+        .await
+}
+
+/// Usage with hedged requests for a latency-sensitive GET
+async fn hedged_request(hedge: HedgeConfig) -> Result<reqwest::Response, RetryError> {
+    // Share one config per endpoint so the latency samples accumulate, e.g.
+    // HedgeConfig::new().latency_percentile(0.95).delay(Duration::from_millis(50))
+    Client::new()
+        .get("https://api.example.com/search?q=rust")
+        .or_hedge_with(RetryConfig::new().max_retries(2), hedge)
+        .await
+}
+
+/// Usage with the `tracing` feature: every attempt shows up as a child span
+#[cfg(feature = "tracing")]
+async fn traced_request() -> Result<reqwest::Response, RetryError> {
+    // With a subscriber exporting to Jaeger installed, this produces a `retry` span with
+    // `method`, `url`, `attempts` and `outcome`, and one `attempt` child span per try with
+    // `attempt`, `reason`, `status`, `delay_ms` and `outcome`
+    Client::new()
+        .get("https://api.example.com/data")
+        .or_retry()
+        .await
+}
+
+/// Usage with the `metrics` feature: install any `metrics` recorder, e.g. a Prometheus exporter
+#[cfg(feature = "metrics")]
+async fn measured_request() -> Result<reqwest::Response, RetryError> {
+    // Emits `reqwest_retry_attempts_total`, `reqwest_retry_retries_total`,
+    // `reqwest_retry_exhausted_total`, `reqwest_retry_circuit_state`,
+    // `reqwest_retry_delay_seconds` and `reqwest_retry_attempt_duration_seconds`,
+    // labelled by `host` and `method` (and `reason` for retries and delays)
+    Client::new()
+        .get("https://api.example.com/data")
+        .or_retry()
+        .await
+}
+
+/// Usage with an async hook refreshing an expired OAuth token before retrying
+async fn refresh_token_before_retry() -> Result<reqwest::Response, RetryError> {
+    use reqwest::header::{HeaderValue, AUTHORIZATION};
+
+    async fn fetch_token() -> Option<String> {
+        // Call the identity provider here
+        Some("new-token".to_string())
+    }
+
+    let config = RetryConfig::new()
+        .should_retry_response(|response| {
+            response.status() == reqwest::StatusCode::UNAUTHORIZED
+                || response.status().is_server_error()
+        })
+        .before_retry(|attempt, mut request| async move {
+            if attempt.response_status == Some(401) {
+                // Give up if the token cannot be refreshed
+                let token = fetch_token().await?;
+                let value = HeaderValue::from_str(&format!("Bearer {token}")).ok()?;
+                request.headers_mut().insert(AUTHORIZATION, value);
+            }
+            Some(request)
+        });
+
+    Client::new()
+        .get("https://api.example.com/data")
+        .bearer_auth("cached-token")
+        .or_retry_with(config)
+        .await
+}
+
+/// Usage with failover across regional endpoints
+async fn regional_failover(
+    failover: std::sync::Arc<Failover>,
+) -> Result<reqwest::Response, RetryError> {
+    // Create the failover once so endpoint health is shared, e.g.
+    // Arc::new(Failover::new([eu_url, us_url]).mode(FailoverMode::RoundRobin))
+    Client::new()
+        .get("https://eu.api.example.com/v1/orders")
+        .or_retry_with(RetryConfig::new().failover(failover).on_retry(|attempt| {
+            if let Some(endpoint) = &attempt.endpoint {
+                println!("Retrying against {endpoint}");
+            }
+        }))
+        .await
+}
+
+/// Usage with streaming uploads: buffer small streams, or reopen the source per attempt
+async fn retry_streaming_upload(
+    stream: reqwest::Body,
+    path: std::path::PathBuf,
+) -> Result<(), RetryError> {
+    // Tee the stream into a buffer of up to 1 MiB and replay it on retries
+    Client::new()
+        .put("https://api.example.com/upload")
+        .body(stream)
+        .or_retry_with(RetryConfig::new().buffer_body(1024 * 1024))
+        .await?;
+
+    // Produce a fresh body for every attempt
+    Client::new()
+        .put("https://api.example.com/upload")
+        .or_retry()
+        .body_factory(move || reqwest::Body::from(std::fs::read(&path).unwrap_or_default()))
+        .await?;
+
+    Ok(())
+}
+
+/// Usage with arbitrary operations, such as sending and decoding in one go
+async fn retry_send_and_decode() -> Result<serde_json::Value, OperationError<reqwest::Error>> {
+    let client = Client::new();
+
+    // Any error type implementing `Retryable` can be retried with the same config
+    retry(&RetryConfig::new().max_retries(3), || async {
+        client
+            .get("https://api.example.com/data")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    })
+    .await
+}
+
+/// Usage with body reads and JSON decoding inside the retry loop
+async fn retry_json_body() -> Result<serde_json::Value, RetryError> {
+    // Truncated bodies are retried, but only once
+    Client::new()
+        .get("https://api.example.com/data")
+        .or_retry_json_with(RetryConfig::new().error_strategy(
+            RetryReason::DecodeError,
+            ErrorStrategy::new().max_retries(1),
+        ))
+        .await
+}
+
+/// Usage with fine-grained reasons and status-specific strategies
+async fn status_specific_strategies() -> Result<reqwest::Response, RetryError> {
+    let config = RetryConfig::new()
+        // DNS failures rarely resolve quickly; other network errors keep the defaults
+        .error_strategy(RetryReason::Dns, ErrorStrategy::new().max_retries(1))
+        // A 503 is usually maintenance: wait longer than for other server errors
+        .status_strategy(
+            reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            ErrorStrategy::new()
+                .max_retries(5)
+                .base_delay(Duration::from_secs(2)),
+        )
+        // A 502 from the gateway is worth a single quick retry
+        .status_strategy(
+            reqwest::StatusCode::BAD_GATEWAY,
+            ErrorStrategy::new().max_retries(1),
+        );
+
+    Client::new()
+        .get("https://api.example.com/data")
+        .or_retry_with(config)
+        .await
+}
+
+/// Usage with retry parameters loaded from a config file
+#[cfg(feature = "serde")]
+fn config_from_file(json: &str) -> Result<RetryConfig, Box<dyn std::error::Error>> {
+    // Any serde format works the same, e.g. TOML or YAML
+    let spec: RetryPolicySpec = serde_json::from_str(json)?;
+
+    // Keep callbacks defined in code and let the file tune the numbers
+    let config = spec.apply(RetryConfig::new().on_retry(|attempt| {
+        println!("Retrying after {:?}", attempt.delay);
+    }))?;
+    Ok(config)
+}
+
+/// Usage with one config shared by many requests
+async fn shared_config(urls: &[&str]) -> Result<(), RetryError> {
+    let config = std::sync::Arc::new(RetryConfig::new().max_retries(2));
+    println!("Effective retry policy: {config:#?}");
+
+    let client = Client::new();
+    for url in urls {
+        client.get(*url).or_retry_with(config.clone()).await?;
+    }
+    Ok(())
+}
+
+/// Usage with configs selected per upstream and route
+async fn retry_with_registry() -> Result<reqwest::Response, RetryError> {
+    // Build the registry once, e.g. at startup, and share it across call sites
+    let registry = RetryPolicyRegistry::new()
+        // Payments are only retried once, and only with an idempotency key
+        .route(
+            RouteMatcher::new()
+                .host("api.payments.example.com")
+                .path_prefix("/v1/charges")
+                .method(reqwest::Method::POST),
+            RetryConfig::new().max_retries(1).generate_idempotency_key(),
+        )
+        // Internal services are close by and recover quickly
+        .route(
+            RouteMatcher::new().host("*.internal.example.com"),
+            RetryConfig::new()
+                .max_retries(5)
+                .base_delay(Duration::from_millis(20)),
+        )
+        .default_config(RetryConfig::new().deadline(Duration::from_secs(10)));
+
+    Client::new()
+        .post("https://api.payments.example.com/v1/charges")
+        .or_retry_registry(&registry)
+        .await
+}
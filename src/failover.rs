@@ -0,0 +1,118 @@
+use reqwest::Url;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Order in which [`Failover`] endpoints are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailoverMode {
+    /// Always prefer the first healthy endpoint in the list
+    #[default]
+    Ordered,
+    /// Rotate the starting endpoint on every selection
+    RoundRobin,
+}
+
+/// Set of equivalent endpoints that attempts are spread over, shared through an `Arc`
+///
+/// Every attempt is sent to the selected endpoint, keeping the request path and query.
+/// An endpoint with a path of its own is a prefix: with `https://eu.example.com/api/v2/`,
+/// a request for `/orders?x=1` goes to `https://eu.example.com/api/v2/orders?x=1`.
+/// An endpoint whose attempt failed is deprioritized for the `penalty` period; when all
+/// endpoints are penalized, the one that failed longest ago is used.
+#[derive(Debug)]
+pub struct Failover {
+    endpoints: Vec<Url>,
+    mode: FailoverMode,
+    penalty: Duration,
+    inner: Mutex<FailoverInner>,
+}
+
+#[derive(Debug)]
+struct FailoverInner {
+    /// Next starting position for round-robin selection
+    next: usize,
+    /// When each endpoint last failed, if it has not succeeded since
+    failed_at: Vec<Option<Instant>>,
+}
+
+impl Failover {
+    /// Create a failover over the given base URLs
+    pub fn new(endpoints: impl IntoIterator<Item = Url>) -> Self {
+        let endpoints: Vec<Url> = endpoints.into_iter().collect();
+        let failed_at = vec![None; endpoints.len()];
+
+        Self {
+            endpoints,
+            mode: FailoverMode::default(),
+            penalty: Duration::from_secs(30),
+            inner: Mutex::new(FailoverInner { next: 0, failed_at }),
+        }
+    }
+
+    /// Set the order in which endpoints are tried
+    pub fn mode(mut self, mode: FailoverMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set how long an endpoint is deprioritized after a failed attempt
+    pub fn penalty(mut self, penalty: Duration) -> Self {
+        self.penalty = penalty;
+        self
+    }
+
+    /// Endpoints in their configured order
+    pub fn endpoints(&self) -> &[Url] {
+        &self.endpoints
+    }
+
+    /// Pick the endpoint for the next attempt
+    pub(crate) fn select(&self) -> Option<usize> {
+        let count = self.endpoints.len();
+        if count == 0 {
+            return None;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let start = match self.mode {
+            FailoverMode::Ordered => 0,
+            FailoverMode::RoundRobin => {
+                let start = inner.next % count;
+                inner.next = inner.next.wrapping_add(1);
+                start
+            }
+        };
+        let candidates = (0..count).map(|offset| (start + offset) % count);
+
+        // Prefer healthy endpoints, then the one whose failure is oldest
+        let healthy = |index: &usize| {
+            inner.failed_at[*index].is_none_or(|failed| failed.elapsed() >= self.penalty)
+        };
+        candidates
+            .clone()
+            .find(healthy)
+            .or_else(|| candidates.min_by_key(|index| inner.failed_at[*index]))
+    }
+
+    /// Record the outcome of an attempt sent to `index`
+    pub(crate) fn record(&self, index: usize, failure: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(failed_at) = inner.failed_at.get_mut(index) {
+            *failed_at = failure.then(Instant::now);
+        }
+    }
+
+    /// Base URL of the endpoint at `index`
+    pub(crate) fn endpoint(&self, index: usize) -> &Url {
+        &self.endpoints[index]
+    }
+
+    /// Point `url` at the endpoint at `index`, appending its path to the endpoint's own
+    pub(crate) fn apply(&self, index: usize, url: &mut Url) {
+        let mut rewritten = self.endpoints[index].clone();
+        let prefix = rewritten.path().trim_end_matches('/');
+        rewritten.set_path(&format!("{prefix}{}", url.path()));
+        rewritten.set_query(url.query());
+        *url = rewritten;
+    }
+}
@@ -0,0 +1,295 @@
+use crate::config::RetryConfig;
+use crate::error::RetryError;
+use crate::tracker::{Decision, PendingAttempt, RetryTracker};
+use reqwest::{Client, Error as ReqwestError, Request, Response};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{sleep, sleep_until, Instant, Sleep};
+
+type AttemptFuture = Pin<Box<dyn Future<Output = Result<Response, ReqwestError>> + Send>>;
+type HookFuture = Pin<Box<dyn Future<Output = Option<Request>> + Send>>;
+
+/// Configuration for speculative hedged requests
+///
+/// Clones share the latency samples used by [`latency_percentile`](Self::latency_percentile),
+/// so one config can be reused across requests to the same endpoint.
+#[derive(Clone)]
+pub struct HedgeConfig {
+    /// Delay without a response before the next hedge is sent
+    pub delay: Duration,
+    /// Latency percentile (0.0 to 1.0) used as the delay once enough samples were seen
+    pub percentile: Option<f64>,
+    /// Samples needed before the percentile replaces the fixed delay
+    pub min_samples: usize,
+    /// Number of recent latencies kept for the percentile
+    pub sample_size: usize,
+    latencies: Arc<Mutex<VecDeque<Duration>>>,
+}
+
+impl Default for HedgeConfig {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(100),
+            percentile: None,
+            min_samples: 20,
+            sample_size: 1000,
+            latencies: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+}
+
+impl HedgeConfig {
+    /// Create a new HedgeConfig with default values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the fixed delay before each hedge
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Hedge once a request takes longer than this percentile of recent latencies, e.g. `0.95`
+    pub fn latency_percentile(mut self, percentile: f64) -> Self {
+        self.percentile = Some(percentile.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Set how many samples are needed before the percentile is used
+    pub fn min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Set how many recent latencies are kept for the percentile
+    pub fn sample_size(mut self, sample_size: usize) -> Self {
+        self.sample_size = sample_size.max(1);
+        self
+    }
+
+    /// Delay to wait before sending the next hedge
+    pub fn hedge_delay(&self) -> Duration {
+        let Some(percentile) = self.percentile else {
+            return self.delay;
+        };
+
+        let latencies = self.latencies.lock().unwrap();
+        if latencies.is_empty() || latencies.len() < self.min_samples {
+            return self.delay;
+        }
+
+        let mut sorted: Vec<Duration> = latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() - 1) as f64 * percentile).round() as usize;
+        sorted[index]
+    }
+
+    /// Record the latency of a completed attempt
+    fn record_latency(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() >= self.sample_size {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+}
+
+/// Future that sends hedged copies of a request and resolves with the first usable outcome
+///
+/// Every copy is an attempt of the same retried request: it is checked against the retry
+/// limits, deadline and circuit breaker before it is sent, and its outcome is classified
+/// like any other. A failed copy is only replaced after the delay its retry was given.
+/// Copies sent early, once the hedge delay passed without an outcome, count as retries,
+/// so no more than `max_retries + 1` copies are sent in total. Requests with a buffered
+/// streaming body are not hedged.
+pub struct HedgeFuture {
+    client: Client,
+    request: Option<Request>,
+    build_error: Option<ReqwestError>,
+    config: Arc<RetryConfig>,
+    hedge: HedgeConfig,
+    tracker: RetryTracker,
+    hedging: bool,
+    launched: bool,
+    in_flight: Vec<(PendingAttempt, AttemptFuture)>,
+    hedge_timer: Option<Pin<Box<Sleep>>>,
+    retry_timer: Option<Pin<Box<Sleep>>>,
+    hook: Option<HookFuture>,
+}
+
+impl HedgeFuture {
+    pub(crate) fn new(
+        request_builder: reqwest::RequestBuilder,
+        config: Arc<RetryConfig>,
+        hedge: HedgeConfig,
+    ) -> Self {
+        let (client, request) = request_builder.build_split();
+        let (mut request, build_error) = match request {
+            Ok(request) => (Some(request), None),
+            Err(error) => (None, Some(error)),
+        };
+        let tracker = RetryTracker::start(&config, request.as_mut());
+        let hedging = tracker.may_hedge();
+
+        Self {
+            client,
+            request,
+            build_error,
+            config,
+            hedge,
+            tracker,
+            hedging,
+            launched: false,
+            in_flight: Vec::new(),
+            hedge_timer: None,
+            retry_timer: None,
+            hook: None,
+        }
+    }
+
+    /// Send another copy of the request and restart the hedge delay
+    #[allow(clippy::result_large_err)]
+    fn launch(&mut self) -> Result<(), RetryError> {
+        let template = self
+            .request
+            .as_ref()
+            .ok_or(RetryError::RequestBuilderNotAvailable)?;
+        let request = self.tracker.next_request(&self.config, template)?;
+
+        let future: AttemptFuture = Box::pin(self.tracker.instrument(self.client.execute(request)));
+        self.in_flight
+            .push((self.tracker.suspend_attempt(), future));
+        self.launched = true;
+
+        let deadline = Instant::now() + self.hedge.hedge_delay();
+        match self.hedge_timer.as_mut() {
+            Some(timer) => timer.as_mut().reset(deadline),
+            None => self.hedge_timer = Some(Box::pin(sleep_until(deadline))),
+        }
+        Ok(())
+    }
+
+    /// Send a copy, giving up on failure only when no other copy is left to wait for
+    #[allow(clippy::result_large_err)]
+    fn try_launch(&mut self) -> Result<(), RetryError> {
+        match self.launch() {
+            Err(error) if self.in_flight.is_empty() => Err(error),
+            Err(_) => {
+                self.hedging = false;
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Whether the hedge delay passed and another copy may be sent early
+    ///
+    /// A copy sent early uses up one of the retries; hedging stops once none is left.
+    fn hedge_due(&mut self, cx: &mut Context<'_>) -> bool {
+        let due = self.hedging
+            && self.request.is_some()
+            && !self.in_flight.is_empty()
+            && self
+                .hedge_timer
+                .as_mut()
+                .is_some_and(|timer| timer.as_mut().poll(cx).is_ready());
+        if due && !self.tracker.reserve_hedge(&self.config) {
+            self.hedging = false;
+            return false;
+        }
+        due
+    }
+}
+
+impl Future for HedgeFuture {
+    type Output = Result<Response, RetryError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        // A request that failed to build can never succeed
+        if let Some(error) = this.build_error.take() {
+            return Poll::Ready(Err(RetryError::NonRetryableError(error)));
+        }
+
+        loop {
+            // Send the first attempt, or a hedge once the others took too long
+            if !this.launched || this.hedge_due(cx) {
+                if let Err(error) = this.try_launch() {
+                    return Poll::Ready(Err(error));
+                }
+                continue;
+            }
+
+            // Once a retry's delay passed, let the hook adjust the request before sending it;
+            // while an earlier retry's hook still holds the request, its launch waits for it
+            if this.hook.is_none()
+                && let Some(timer) = this.retry_timer.as_mut()
+                && timer.as_mut().poll(cx).is_ready()
+            {
+                this.retry_timer = None;
+                match (&this.config.before_retry, this.tracker.last_retry()) {
+                    (Some(hook), Some(attempt)) => match this.request.take() {
+                        Some(request) => this.hook = Some(hook(attempt.clone(), request)),
+                        None => return Poll::Ready(Err(RetryError::RequestBuilderNotAvailable)),
+                    },
+                    _ => {
+                        if let Err(error) = this.try_launch() {
+                            return Poll::Ready(Err(error));
+                        }
+                    }
+                }
+                continue;
+            }
+            if let Some(hook) = this.hook.as_mut()
+                && let Poll::Ready(request) = hook.as_mut().poll(cx)
+            {
+                this.hook = None;
+                match request {
+                    Some(request) => this.request = Some(request),
+                    None => return Poll::Ready(Err(this.tracker.cancel())),
+                }
+                if let Err(error) = this.try_launch() {
+                    return Poll::Ready(Err(error));
+                }
+                continue;
+            }
+
+            // Take the first attempt that completed, dropping it from the in-flight set
+            let completed =
+                this.in_flight
+                    .iter_mut()
+                    .enumerate()
+                    .find_map(|(index, (_, future))| match future.as_mut().poll(cx) {
+                        Poll::Ready(result) => Some((index, result)),
+                        Poll::Pending => None,
+                    });
+            let Some((index, result)) = completed else {
+                return Poll::Pending;
+            };
+            let (attempt, _) = this.in_flight.swap_remove(index);
+            let latency = attempt.elapsed();
+            this.tracker.resume_attempt(attempt);
+
+            let decision = match result {
+                Ok(response) => this.tracker.on_response(&this.config, response),
+                Err(error) => this.tracker.on_error(&this.config, error),
+            };
+            match decision {
+                // A retry decided while another one waits takes over its delay
+                Decision::Retry(delay) => this.retry_timer = Some(Box::pin(sleep(delay))),
+                // The first final outcome wins and the remaining copies are cancelled
+                Decision::Done(result) => {
+                    if result.is_ok() {
+                        this.hedge.record_latency(latency);
+                    }
+                    return Poll::Ready(result);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::RetryReason;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Outcome of a single request attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    /// The server answered with this status code
+    Status(u16),
+    /// The request failed before a response was received
+    Error(String),
+}
+
+/// Record of a single attempt made while retrying
+#[derive(Debug, Clone)]
+pub struct AttemptRecord {
+    /// Attempt number (0-based)
+    pub attempt: usize,
+    /// Status code or error produced by the attempt
+    pub outcome: AttemptOutcome,
+    /// Reason the outcome was classified as
+    pub reason: RetryReason,
+    /// Delay slept after this attempt (zero if no retry followed)
+    pub delay: Duration,
+    /// Wall-clock time at which the attempt was started
+    pub timestamp: SystemTime,
+    /// Time the attempt took until its outcome was known
+    pub elapsed: Duration,
+}
+
+/// Every attempt made for one logical request
+#[derive(Debug, Clone, Default)]
+pub struct RetryHistory {
+    /// Attempts in the order they were made
+    pub attempts: Vec<AttemptRecord>,
+    /// Total wall time spent, including delays between attempts
+    pub total_elapsed: Duration,
+}
+
+impl RetryHistory {
+    /// Record the outcome of an attempt started at `started`
+    pub(crate) fn push(
+        &mut self,
+        outcome: AttemptOutcome,
+        reason: RetryReason,
+        started: (SystemTime, Instant),
+    ) {
+        self.attempts.push(AttemptRecord {
+            attempt: self.attempts.len(),
+            outcome,
+            reason,
+            delay: Duration::ZERO,
+            timestamp: started.0,
+            elapsed: started.1.elapsed(),
+        });
+    }
+
+    /// Record the delay slept after the most recent attempt
+    pub(crate) fn set_last_delay(&mut self, delay: Duration) {
+        if let Some(last) = self.attempts.last_mut() {
+            last.delay = delay;
+        }
+    }
+
+    /// Take the collected history, stamping the total time since `started`
+    pub(crate) fn finish(&mut self, started: Option<Instant>) -> RetryHistory {
+        self.total_elapsed = started.map_or(Duration::ZERO, |started| started.elapsed());
+        std::mem::take(self)
+    }
+}
@@ -0,0 +1,58 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A shared function hook together with the name `Debug` shows for it
+///
+/// Builders label hooks with the name of their type, which is not descriptive for
+/// closures; [`with_label`](Self::with_label) replaces it. The hook is called through
+/// `Deref`, e.g. `(config.should_retry)(&error)`.
+pub struct Hook<F: ?Sized> {
+    f: Arc<F>,
+    label: Cow<'static, str>,
+}
+
+impl<F: ?Sized> Hook<F> {
+    /// Wrap `f`, shown as `label` by `Debug`
+    pub fn new(f: Arc<F>, label: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            f,
+            label: label.into(),
+        }
+    }
+
+    /// Set the name `Debug` shows for the hook
+    pub fn with_label(mut self, label: impl Into<Cow<'static, str>>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    /// Name `Debug` shows for the hook
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            label: self.label.clone(),
+        }
+    }
+}
+
+impl<F: ?Sized> Deref for Hook<F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.f
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.label)
+    }
+}
@@ -1,13 +1,52 @@
-use reqwest::{Error as ReqwestError, Response, StatusCode};
-use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderName, RETRY_AFTER};
+use reqwest::{Body, Error as ReqwestError, Request, Response, StatusCode};
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+mod budget;
+mod circuit_breaker;
 mod config;
+mod decode;
+mod drive;
 mod error;
+mod failover;
+mod hedge;
+mod history;
+mod hook;
+#[cfg(feature = "middleware")]
+mod middleware;
+mod operation;
+mod registry;
+mod replay;
 mod retry_future;
+mod retry_metrics;
+mod spans;
+#[cfg(feature = "serde")]
+mod spec;
+#[cfg(feature = "tower")]
+mod tower;
+mod tracker;
 mod trait_impl;
+pub use budget::RetryBudget;
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
 pub use config::RetryConfig;
+pub use decode::DecodeFuture;
 pub use error::RetryError;
+pub use failover::{Failover, FailoverMode};
+pub use hedge::{HedgeConfig, HedgeFuture};
+pub use history::{AttemptOutcome, AttemptRecord, RetryHistory};
+pub use hook::Hook;
+#[cfg(feature = "middleware")]
+pub use middleware::RetryMiddleware;
+pub use operation::{retry, OperationError, Retryable};
+pub use registry::{RetryPolicyRegistry, RouteMatcher};
 pub use retry_future::RetryFuture;
+#[cfg(feature = "serde")]
+pub use spec::{BackoffKind, ErrorStrategySpec, PolicySpecError, RetryPolicySpec};
+#[cfg(feature = "tower")]
+pub use tower::{BoxError, RetryLayer, RetryService};
 pub use trait_impl::RetryExt;
 pub mod backoff;
 pub mod predicates;
@@ -19,6 +58,9 @@ mod examples;
 #[cfg(test)]
 mod tests;
 
+/// Header marking a request as safe to repeat, as used by Stripe-style APIs
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
 /// Information about the current retry attempt
 #[derive(Debug, Clone)]
 pub struct RetryAttempt {
@@ -34,6 +76,12 @@ pub struct RetryAttempt {
     pub response_status: Option<u16>,
     /// The type of error that triggered this retry
     pub error_type: RetryReason,
+    /// Delay requested by the server through a `Retry-After` header (if any)
+    pub retry_after: Option<Duration>,
+    /// Whether retrying was stopped because the shared retry budget is spent
+    pub budget_exhausted: bool,
+    /// Base URL of the endpoint the retry is sent to, when failing over across endpoints
+    pub endpoint: Option<String>,
 }
 
 /// The reason why a retry is being attempted
@@ -47,17 +95,123 @@ pub enum RetryReason {
     RateLimit,
     /// Request error (malformed request, etc.)
     RequestError,
+    /// Timed out while establishing the connection
+    ConnectTimeout,
+    /// Timed out while waiting for or reading the response
+    ReadTimeout,
+    /// Host name could not be resolved
+    Dns,
+    /// TLS handshake or certificate failure
+    ///
+    /// TLS backends give no reliable error shape, so the default classifier reports these
+    /// as [`NetworkError`](Self::NetworkError); a custom error classifier may use this one.
+    Tls,
+    /// Connection reset or aborted by the peer
+    ConnectionReset,
+    /// Too many redirects, usually a redirect loop
+    RedirectLoop,
+    /// Failure while reading the response body
+    BodyError,
+    /// Response body could not be decoded
+    DecodeError,
     /// Custom error type defined by user
     Custom(String),
 }
 
+impl RetryReason {
+    /// Snake-case name of the reason, or the name of a custom reason
+    pub fn name(&self) -> &str {
+        match self {
+            RetryReason::NetworkError => "network_error",
+            RetryReason::ServerError => "server_error",
+            RetryReason::RateLimit => "rate_limit",
+            RetryReason::RequestError => "request_error",
+            RetryReason::ConnectTimeout => "connect_timeout",
+            RetryReason::ReadTimeout => "read_timeout",
+            RetryReason::Dns => "dns",
+            RetryReason::Tls => "tls",
+            RetryReason::ConnectionReset => "connection_reset",
+            RetryReason::RedirectLoop => "redirect_loop",
+            RetryReason::BodyError => "body_error",
+            RetryReason::DecodeError => "decode_error",
+            RetryReason::Custom(name) => name,
+        }
+    }
+
+    /// Reason with the given [name](RetryReason::name), or a custom reason
+    pub fn from_name(name: &str) -> RetryReason {
+        match name {
+            "network_error" => RetryReason::NetworkError,
+            "server_error" => RetryReason::ServerError,
+            "rate_limit" => RetryReason::RateLimit,
+            "request_error" => RetryReason::RequestError,
+            "connect_timeout" => RetryReason::ConnectTimeout,
+            "read_timeout" => RetryReason::ReadTimeout,
+            "dns" => RetryReason::Dns,
+            "tls" => RetryReason::Tls,
+            "connection_reset" => RetryReason::ConnectionReset,
+            "redirect_loop" => RetryReason::RedirectLoop,
+            "body_error" => RetryReason::BodyError,
+            "decode_error" => RetryReason::DecodeError,
+            name => RetryReason::Custom(name.to_string()),
+        }
+    }
+
+    /// Broader reason whose strategy applies when none is set for this one
+    pub fn broader(&self) -> Option<RetryReason> {
+        match self {
+            RetryReason::ConnectTimeout
+            | RetryReason::ReadTimeout
+            | RetryReason::Dns
+            | RetryReason::Tls
+            | RetryReason::ConnectionReset => Some(RetryReason::NetworkError),
+            RetryReason::RedirectLoop => Some(RetryReason::RequestError),
+            _ => None,
+        }
+    }
+}
+
+/// Backoff calculation function: `(attempt, base_delay, multiplier, max_delay) -> delay`
+pub type BackoffFn = Arc<dyn Fn(usize, Duration, f64, Duration) -> Duration + Send + Sync>;
+
+/// Factory creating the per-request [`backoff::Backoff`] state
+pub type BackoffFactory = Hook<dyn Fn() -> Box<dyn backoff::Backoff> + Send + Sync>;
+
+/// Predicate deciding whether an error should trigger a retry
+pub type ErrorPredicate = Hook<dyn Fn(&ReqwestError) -> bool + Send + Sync>;
+
+/// Predicate deciding whether a response should trigger a retry
+pub type ResponsePredicate = Hook<dyn Fn(&Response) -> bool + Send + Sync>;
+
+/// Classifier mapping an error to a retry reason
+pub type ErrorClassifier = Hook<dyn Fn(&ReqwestError) -> RetryReason + Send + Sync>;
+
+/// Classifier mapping a response to a retry reason
+pub type ResponseClassifier = Hook<dyn Fn(&Response) -> RetryReason + Send + Sync>;
+
+/// Generator producing the `Idempotency-Key` of one logical request
+pub type IdempotencyKeyFn = Hook<dyn Fn() -> String + Send + Sync>;
+
+/// Callback observing a retry attempt
+pub type RetryCallback = Hook<dyn Fn(&RetryAttempt) + Send + Sync>;
+
+/// Factory producing a fresh request body for every attempt
+pub type BodyFactory = Arc<dyn Fn() -> Body + Send + Sync>;
+
+/// Async hook run before each retry, returning the next request or `None` to stop retrying
+pub type BeforeRetryHook = Hook<
+    dyn Fn(RetryAttempt, Request) -> Pin<Box<dyn Future<Output = Option<Request>> + Send>>
+        + Send
+        + Sync,
+>;
+
 /// Error-specific retry strategy
-#[derive(Clone)]
+#[derive(Clone, Debug, Default)]
 pub struct ErrorStrategy {
     /// Maximum retries for this error type
     pub max_retries: Option<usize>,
-    /// Custom backoff function for this error type
-    pub backoff_fn: Option<fn(usize, Duration, f64, Duration) -> Duration>,
+    /// Custom backoff strategy for this error type
+    pub backoff: Option<BackoffFactory>,
     /// Base delay override for this error type
     pub base_delay: Option<Duration>,
     /// Max delay override for this error type
@@ -66,25 +220,20 @@ pub struct ErrorStrategy {
     pub backoff_multiplier: Option<f64>,
 }
 
-impl Default for ErrorStrategy {
-    fn default() -> Self {
-        Self {
-            max_retries: None,
-            backoff_fn: None,
-            base_delay: None,
-            max_delay: None,
-            backoff_multiplier: None,
-        }
-    }
-}
-
 /// Default implementation for determining if an error should trigger a retry
 fn default_should_retry_error(error: &ReqwestError) -> bool {
     // Retry on network errors, timeouts, and some server errors
     error.is_timeout()
         || error.is_connect()
         || error.is_request()
-        || (error.status().map_or(false, |s| s.is_server_error()))
+        || error.is_body()
+        || (error.is_decode() && json_error(error).is_none_or(serde_json::Error::is_eof))
+        || (error.status().is_some_and(|s| s.is_server_error()))
+}
+
+/// JSON error behind a decode error; other decode errors come from reading the body
+fn json_error(error: &ReqwestError) -> Option<&serde_json::Error> {
+    std::error::Error::source(error)?.downcast_ref()
 }
 
 /// Default implementation for determining if a response should trigger a retry
@@ -108,7 +257,19 @@ fn default_response_classifier(response: &Response) -> RetryReason {
 
 /// Default error classifier for reqwest errors
 fn default_error_classifier(error: &ReqwestError) -> RetryReason {
-    if error.is_timeout() || error.is_connect() {
+    if error.is_redirect() {
+        RetryReason::RedirectLoop
+    } else if error.is_timeout() && error.is_connect() {
+        RetryReason::ConnectTimeout
+    } else if error.is_timeout() {
+        RetryReason::ReadTimeout
+    } else if error.is_body() || (error.is_decode() && json_error(error).is_none()) {
+        RetryReason::BodyError
+    } else if error.is_decode() {
+        RetryReason::DecodeError
+    } else if let Some(reason) = network_error_reason(error) {
+        reason
+    } else if error.is_connect() {
         RetryReason::NetworkError
     } else if error.is_request() {
         RetryReason::RequestError
@@ -124,6 +285,57 @@ fn default_error_classifier(error: &ReqwestError) -> RetryReason {
         RetryReason::NetworkError
     }
 }
+
+/// Find a DNS or connection reset failure in the source chain of `error`
+///
+/// Only shapes known from reqwest's connector are matched: I/O error kinds and the message
+/// hyper-util gives failed lookups.
+fn network_error_reason(error: &ReqwestError) -> Option<RetryReason> {
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            match io.kind() {
+                ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe => return Some(RetryReason::ConnectionReset),
+                ErrorKind::ConnectionRefused => return None,
+                _ => {}
+            }
+        }
+
+        // Resolver errors come from a crate this one does not depend on
+        if error.is_connect() && cause.to_string() == "dns error" {
+            return Some(RetryReason::Dns);
+        }
+
+        source = cause.source();
+    }
+    None
+}
+
+/// Parse a `Retry-After` header in either delta-seconds or HTTP-date form
+fn parse_retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // A date in the past means the client may retry immediately
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+/// Extract the server-requested delay from a 429 or 503 response
+fn response_retry_after(response: &Response) -> Option<Duration> {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+        parse_retry_after(response.headers(), SystemTime::now())
+    } else {
+        None
+    }
+}
+
 /// Default exponential backoff calculation
 fn default_backoff(
     attempt: usize,
@@ -148,12 +360,33 @@ impl ErrorStrategy {
         self
     }
 
+    /// Set custom backoff strategy for this error type
+    pub fn backoff<B>(mut self, backoff: B) -> Self
+    where
+        B: backoff::Backoff + Clone + Sync + 'static,
+    {
+        self.backoff = Some(backoff::factory(backoff));
+        self
+    }
+
+    /// Set custom factory creating the backoff state for this error type
+    pub fn backoff_factory(
+        mut self,
+        factory: impl Fn() -> Box<dyn backoff::Backoff> + Send + Sync + 'static,
+    ) -> Self {
+        let label = std::any::type_name_of_val(&factory);
+        self.backoff = Some(Hook::new(Arc::new(factory), label));
+        self
+    }
+
     /// Set custom backoff function for this error type
     pub fn backoff_fn(
         mut self,
-        backoff_fn: fn(usize, Duration, f64, Duration) -> Duration,
+        backoff_fn: impl Fn(usize, Duration, f64, Duration) -> Duration + Send + Sync + 'static,
     ) -> Self {
-        self.backoff_fn = Some(backoff_fn);
+        let label = std::any::type_name_of_val(&backoff_fn);
+        self.backoff =
+            Some(backoff::factory(backoff::FnBackoff(Arc::new(backoff_fn))).with_label(label));
         self
     }
 
@@ -182,5 +415,5 @@ struct EffectiveStrategy {
     base_delay: Duration,
     max_delay: Duration,
     backoff_multiplier: f64,
-    backoff_fn: fn(usize, Duration, f64, Duration) -> Duration,
+    backoff: BackoffFactory,
 }
@@ -0,0 +1,82 @@
+use crate::config::RetryConfig;
+use crate::drive::{drive, Attempt};
+use crate::error::RetryError;
+use crate::tracker::RetryTracker;
+use http::Extensions;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Error as MiddlewareError, Middleware, Next, Result};
+use std::sync::{Arc, Mutex};
+
+/// `reqwest-middleware` middleware retrying requests according to a [`RetryConfig`]
+///
+/// Non-retryable reqwest errors are passed through unchanged; every other
+/// [`RetryError`] is returned as [`MiddlewareError::Middleware`] and can be recovered
+/// with `downcast_ref::<RetryError>()`. Errors raised by inner middlewares are never retried.
+pub struct RetryMiddleware {
+    config: Arc<RetryConfig>,
+}
+
+impl RetryMiddleware {
+    /// Create a middleware driven by the given configuration, owned or shared through an `Arc`
+    pub fn new(config: impl Into<Arc<RetryConfig>>) -> Self {
+        Self {
+            config: config.into(),
+        }
+    }
+}
+
+impl From<RetryConfig> for RetryMiddleware {
+    fn from(config: RetryConfig) -> Self {
+        Self::new(config)
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RetryMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let tracker = RetryTracker::start(&self.config, Some(&mut req));
+
+        // Every attempt runs with the same extensions, lent to it while it is in flight
+        let shared = Mutex::new(std::mem::take(extensions));
+        let result = drive(tracker, &self.config, req, |request| {
+            let (next, shared) = (next.clone(), &shared);
+            async move {
+                let mut extensions = std::mem::take(&mut *shared.lock().unwrap());
+                let result = next.run(request, &mut extensions).await;
+                *shared.lock().unwrap() = extensions;
+
+                match result {
+                    Ok(response) => Ok(Attempt::Response(response)),
+                    Err(MiddlewareError::Reqwest(error)) => Ok(Attempt::Error(error)),
+                    Err(error) => Err(HandleError(error)),
+                }
+            }
+        })
+        .await;
+        *extensions = shared.into_inner().unwrap();
+
+        result.map_err(|HandleError(error)| error)
+    }
+}
+
+/// Error ending [`RetryMiddleware::handle`], converted from retry errors
+struct HandleError(MiddlewareError);
+
+impl From<RetryError> for HandleError {
+    fn from(error: RetryError) -> Self {
+        HandleError(into_middleware_error(error))
+    }
+}
+
+/// Convert a retry error into the middleware error type
+fn into_middleware_error(error: RetryError) -> MiddlewareError {
+    match error {
+        RetryError::NonRetryableError(error) => MiddlewareError::Reqwest(error),
+        error => MiddlewareError::middleware(error),
+    }
+}
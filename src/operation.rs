@@ -0,0 +1,117 @@
+use crate::config::RetryConfig;
+use crate::history::RetryHistory;
+use crate::tracker::RetryTracker;
+use crate::{default_error_classifier, default_should_retry_error, RetryReason};
+use reqwest::Error as ReqwestError;
+use std::fmt::Display;
+use std::io::{Error as IoError, ErrorKind};
+use thiserror::Error;
+
+/// Classification of errors produced by operations passed to [`retry`]
+pub trait Retryable {
+    /// Reason used to pick the error strategy and backoff for this error
+    fn retry_reason(&self) -> RetryReason;
+
+    /// Whether another attempt may succeed, by default unless the reason is a request error
+    fn is_retryable(&self) -> bool {
+        self.retry_reason() != RetryReason::RequestError
+    }
+}
+
+impl Retryable for ReqwestError {
+    fn retry_reason(&self) -> RetryReason {
+        default_error_classifier(self)
+    }
+
+    fn is_retryable(&self) -> bool {
+        default_should_retry_error(self)
+    }
+}
+
+impl Retryable for IoError {
+    fn retry_reason(&self) -> RetryReason {
+        match self.kind() {
+            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe => {
+                RetryReason::ConnectionReset
+            }
+            ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::NotConnected
+            | ErrorKind::UnexpectedEof => RetryReason::NetworkError,
+            _ => RetryReason::RequestError,
+        }
+    }
+}
+
+/// Errors of an operation retried with [`retry`]
+#[derive(Error, Debug)]
+pub enum OperationError<E> {
+    #[error("Maximum retry attempts exceeded after {} attempts", .history.attempts.len())]
+    MaxRetriesExceeded { history: RetryHistory },
+    #[error("Operation failed with non-retryable error: {0}")]
+    NonRetryableError(E),
+    #[error("Operation failed after {} attempts: {source}", .history.attempts.len())]
+    RetriesExhausted { source: E, history: RetryHistory },
+    #[error("Retry deadline exceeded after {} attempts", .history.attempts.len())]
+    DeadlineExceeded { history: RetryHistory },
+    #[error("Circuit breaker is open after {} attempts", .history.attempts.len())]
+    CircuitOpen { history: RetryHistory },
+    #[error("Retry budget exhausted after {} attempts: {source}", .history.attempts.len())]
+    BudgetExhausted { source: E, history: RetryHistory },
+}
+
+impl<E> OperationError<E> {
+    /// Attempt history, if retries were exhausted
+    pub fn history(&self) -> Option<&RetryHistory> {
+        match self {
+            OperationError::MaxRetriesExceeded { history }
+            | OperationError::RetriesExhausted { history, .. }
+            | OperationError::DeadlineExceeded { history }
+            | OperationError::CircuitOpen { history }
+            | OperationError::BudgetExhausted { history, .. } => Some(history),
+            OperationError::NonRetryableError(_) => None,
+        }
+    }
+
+    /// Take the error of the last attempt, if the operation gave up because of it
+    pub fn into_inner(self) -> Option<E> {
+        match self {
+            OperationError::NonRetryableError(source)
+            | OperationError::RetriesExhausted { source, .. }
+            | OperationError::BudgetExhausted { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Run `operation` until it succeeds or `config` gives up on its errors
+///
+/// Errors are classified through [`Retryable`] and retried with the same strategies,
+/// callbacks, deadline, circuit breaker and retry budget as requests. Attempt timeouts,
+/// failover and the `before_retry` hook only apply to requests.
+pub async fn retry<T, E, F, Fut>(
+    config: &RetryConfig,
+    mut operation: F,
+) -> Result<T, OperationError<E>>
+where
+    E: Retryable + Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut tracker = RetryTracker::new(true, None);
+    loop {
+        tracker.begin_operation(config)?;
+        match tracker.instrument(operation()).await {
+            Ok(value) => {
+                tracker.on_success(config);
+                return Ok(value);
+            }
+            Err(error) => {
+                let delay = tracker.on_failure(config, error)?;
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
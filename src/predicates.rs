@@ -15,7 +15,9 @@ pub fn except_client_errors(error: &ReqwestError) -> bool {
 }
 
 /// Custom response predicate for specific status codes
-pub fn retry_on_status(codes: &'static [StatusCode]) -> impl Fn(&Response) -> bool {
+pub fn retry_on_status(
+    codes: &'static [StatusCode],
+) -> impl Fn(&Response) -> bool + Send + Sync + 'static {
     move |response: &Response| codes.contains(&response.status())
 }
 
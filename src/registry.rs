@@ -0,0 +1,113 @@
+use crate::config::RetryConfig;
+use reqwest::{Method, Url};
+use std::sync::Arc;
+
+/// Requests a config in a [`RetryPolicyRegistry`] applies to
+///
+/// Every constraint that is set must match; a matcher without constraints matches all
+/// requests.
+#[derive(Debug, Clone, Default)]
+pub struct RouteMatcher {
+    host: Option<String>,
+    path_prefix: Option<String>,
+    methods: Vec<Method>,
+}
+
+impl RouteMatcher {
+    /// Create a matcher that matches every request
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match a host exactly, or its subdomains with a leading `*.`, e.g. `*.example.com`
+    pub fn host(mut self, pattern: impl Into<String>) -> Self {
+        self.host = Some(pattern.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Match paths starting with the given segments, e.g. `/v1` matches `/v1/orders`
+    pub fn path_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.path_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Match the given method, in addition to any methods matched already
+    pub fn method(mut self, method: Method) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// Whether a request with `method` to `url` matches
+    pub fn matches(&self, method: &Method, url: &Url) -> bool {
+        self.matches_host(url.host_str().unwrap_or_default())
+            && self.matches_path(url.path())
+            && (self.methods.is_empty() || self.methods.contains(method))
+    }
+
+    fn matches_host(&self, host: &str) -> bool {
+        let Some(pattern) = &self.host else {
+            return true;
+        };
+        let host = host.to_ascii_lowercase();
+
+        match pattern.strip_prefix("*.") {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+            None => pattern == "*" || *pattern == host,
+        }
+    }
+
+    fn matches_path(&self, path: &str) -> bool {
+        let Some(prefix) = &self.path_prefix else {
+            return true;
+        };
+
+        // Only match whole segments, so `/v1` does not match `/v10`
+        path.strip_prefix(prefix.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'))
+    }
+}
+
+/// Retry configs selected by the host, path and method of each request
+///
+/// Routes are checked in the order they were added and the first match wins, so more
+/// specific routes belong first. Requests matching no route use the default config.
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicyRegistry {
+    routes: Vec<(RouteMatcher, Arc<RetryConfig>)>,
+    default: Arc<RetryConfig>,
+}
+
+impl RetryPolicyRegistry {
+    /// Create an empty registry using `RetryConfig::default()` for every request
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `config`, owned or shared through an `Arc`, for requests matching `matcher`
+    pub fn route(mut self, matcher: RouteMatcher, config: impl Into<Arc<RetryConfig>>) -> Self {
+        self.routes.push((matcher, config.into()));
+        self
+    }
+
+    /// Set the config used for requests matching no route
+    pub fn default_config(mut self, config: impl Into<Arc<RetryConfig>>) -> Self {
+        self.default = config.into();
+        self
+    }
+
+    /// Config for a request with `method` to `url`
+    pub fn lookup(&self, method: &Method, url: &Url) -> Arc<RetryConfig> {
+        self.routes
+            .iter()
+            .find(|(matcher, _)| matcher.matches(method, url))
+            .map_or(&self.default, |(_, config)| config)
+            .clone()
+    }
+
+    /// Config for requests whose method and URL are unknown
+    pub(crate) fn fallback(&self) -> Arc<RetryConfig> {
+        self.default.clone()
+    }
+}
@@ -0,0 +1,145 @@
+use crate::config::RetryConfig;
+use crate::BodyFactory;
+use bytes::Bytes;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use reqwest::{Body, Error as ReqwestError, Request};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Where the body of every attempt comes from
+pub(crate) enum BodySource {
+    /// The body is cloned along with the request, if it can be
+    Cloned,
+    /// A streaming body is teed into a bounded buffer and replayed on later attempts
+    Buffered(Arc<Mutex<BodyBuffer>>),
+    /// A fresh body is produced for every attempt
+    Factory(BodyFactory),
+}
+
+impl BodySource {
+    /// Take a streaming body out of `request` when the config allows buffering it
+    pub(crate) fn detach(request: &mut Request, config: &RetryConfig) -> Self {
+        let streaming = request.body().is_some_and(|body| body.as_bytes().is_none());
+
+        match config.body_buffer_limit {
+            Some(limit) if streaming => {
+                let source = request.body_mut().take();
+                Self::Buffered(Arc::new(Mutex::new(BodyBuffer {
+                    source,
+                    data: Vec::new(),
+                    limit,
+                    overflowed: false,
+                })))
+            }
+            _ => Self::Cloned,
+        }
+    }
+
+    /// Whether attempts share one buffered stream, so they must not run concurrently
+    pub(crate) fn is_buffered(&self) -> bool {
+        matches!(self, Self::Buffered(_))
+    }
+
+    /// Whether a later attempt can still send the body, as far as is known before cloning
+    pub(crate) fn can_replay(&self) -> bool {
+        match self {
+            Self::Buffered(buffer) => !buffer.lock().unwrap().overflowed,
+            Self::Cloned | Self::Factory(_) => true,
+        }
+    }
+
+    /// Produce the request for the next attempt from the body-less or cloneable `template`
+    pub(crate) fn attempt(&self, template: &Request) -> Option<Request> {
+        let mut request = template.try_clone()?;
+        match self {
+            Self::Cloned => {}
+            Self::Buffered(buffer) => {
+                // Everything read so far must still be buffered to replay it
+                if buffer.lock().unwrap().overflowed {
+                    return None;
+                }
+                *request.body_mut() = Some(Body::wrap(ReplayBody {
+                    buffer: buffer.clone(),
+                    position: 0,
+                }));
+            }
+            Self::Factory(factory) => *request.body_mut() = Some(factory()),
+        }
+        Some(request)
+    }
+}
+
+/// Shared state of a buffered streaming body
+pub(crate) struct BodyBuffer {
+    /// Remainder of the original stream, not yet read by any attempt
+    source: Option<Body>,
+    /// Everything read from the stream so far
+    data: Vec<u8>,
+    limit: usize,
+    overflowed: bool,
+}
+
+/// Body of one attempt: the buffered bytes followed by the rest of the original stream
+struct ReplayBody {
+    buffer: Arc<Mutex<BodyBuffer>>,
+    position: usize,
+}
+
+impl HttpBody for ReplayBody {
+    type Data = Bytes;
+    type Error = ReqwestError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, ReqwestError>>> {
+        let buffer = self.buffer.clone();
+        let mut buffer = buffer.lock().unwrap();
+
+        // Replay what earlier attempts already read
+        if self.position < buffer.data.len() {
+            let chunk = Bytes::copy_from_slice(&buffer.data[self.position..]);
+            self.position = buffer.data.len();
+            return Poll::Ready(Some(Ok(Frame::data(chunk))));
+        }
+
+        let Some(source) = buffer.source.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let frame = match Pin::new(source).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => frame,
+            Poll::Ready(None) => {
+                buffer.source = None;
+                return Poll::Ready(None);
+            }
+            other => return other,
+        };
+
+        // Keep new data for later attempts until the limit is reached
+        if let Some(data) = frame.data_ref() {
+            if !buffer.overflowed && buffer.data.len() + data.len() <= buffer.limit {
+                buffer.data.extend_from_slice(data);
+                self.position = buffer.data.len();
+            } else {
+                buffer.overflowed = true;
+                buffer.data = Vec::new();
+            }
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let buffer = self.buffer.lock().unwrap();
+        let mut hint = buffer
+            .source
+            .as_ref()
+            .map_or_else(|| SizeHint::with_exact(0), HttpBody::size_hint);
+        let buffered = (buffer.data.len() - self.position.min(buffer.data.len())) as u64;
+        if let Some(upper) = hint.upper() {
+            hint.set_upper(upper + buffered);
+        }
+        hint.set_lower(hint.lower() + buffered);
+        hint
+    }
+}
@@ -1,20 +1,22 @@
 use crate::config::RetryConfig;
 use crate::error::RetryError;
-use crate::{EffectiveStrategy, RetryAttempt, RetryReason};
+use crate::replay::BodySource;
+use crate::tracker::{Decision, RetryTracker};
 use pin_project_lite::pin_project;
-use reqwest::{Error as ReqwestError, Response};
+use reqwest::{Body, Client, Error as ReqwestError, Request, Response};
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
 use tokio::time::{sleep, Sleep};
 
 pin_project! {
     /// Future that handles the retry logic
     pub struct RetryFuture {
-        request_builder: Option<reqwest::RequestBuilder>,
-        config: RetryConfig,
-        attempts: usize,
-        current_error_type: Option<RetryReason>,
+        client: Client,
+        request: Option<Request>,
+        build_error: Option<ReqwestError>,
+        config: Arc<RetryConfig>,
+        tracker: RetryTracker,
         #[pin]
         state: RetryState,
     }
@@ -32,20 +34,53 @@ pin_project! {
             #[pin]
             sleep: Sleep,
         },
+        Hooking {
+            #[pin]
+            future: Pin<Box<dyn Future<Output = Option<Request>> + Send>>,
+        },
         Done,
     }
 }
 
 impl RetryFuture {
-    pub(crate) fn new(request_builder: reqwest::RequestBuilder, config: RetryConfig) -> Self {
+    pub(crate) fn new(request_builder: reqwest::RequestBuilder, config: Arc<RetryConfig>) -> Self {
+        let (client, request) = request_builder.build_split();
+        Self::from_parts(client, request, config)
+    }
+
+    /// Create the future from a built request, or the error that building it failed with
+    pub(crate) fn from_parts(
+        client: Client,
+        request: Result<Request, ReqwestError>,
+        config: Arc<RetryConfig>,
+    ) -> Self {
+        let (mut request, build_error) = match request {
+            Ok(request) => (Some(request), None),
+            Err(error) => (None, Some(error)),
+        };
+        let tracker = RetryTracker::start(&config, request.as_mut());
+
         Self {
-            request_builder: Some(request_builder),
+            client,
+            request,
+            build_error,
             config,
-            attempts: 0,
-            current_error_type: None,
+            tracker,
             state: RetryState::Ready,
         }
     }
+
+    /// Produce a fresh body for every attempt, e.g. by reopening a file
+    ///
+    /// The body set on the request builder, if any, is discarded.
+    pub fn body_factory(mut self, factory: impl Fn() -> Body + Send + Sync + 'static) -> Self {
+        if let Some(request) = self.request.as_mut() {
+            request.body_mut().take();
+        }
+        self.tracker
+            .set_body(BodySource::Factory(Arc::new(factory)));
+        self
+    }
 }
 
 impl Future for RetryFuture {
@@ -61,157 +96,65 @@ impl Future for RetryFuture {
 
             let poll_result = match this.state.as_mut().project() {
                 RetryStateProj::Ready => {
-                    // Get effective strategy for current error type
-                    let strategy = if let Some(error_type) = this.current_error_type.as_ref() {
-                        this.config.get_effective_strategy(error_type)
-                    } else {
-                        // First attempt, use default strategy
-                        EffectiveStrategy {
-                            max_retries: this.config.max_retries,
-                            base_delay: this.config.base_delay,
-                            max_delay: this.config.max_delay,
-                            backoff_multiplier: this.config.backoff_multiplier,
-                            backoff_fn: this.config.backoff_fn,
-                        }
-                    };
-
-                    // Check if we've exceeded max retries for this error type
-                    if *this.attempts > strategy.max_retries {
-                        return Poll::Ready(Err(RetryError::MaxRetriesExceeded));
+                    // A request that failed to build can never succeed
+                    if let Some(error) = this.build_error.take() {
+                        return Poll::Ready(Err(RetryError::NonRetryableError(error)));
                     }
 
-                    // Clone the request for this attempt
-                    let request = match this.request_builder.as_ref() {
-                        Some(builder) => match builder.try_clone() {
-                            Some(cloned) => cloned,
-                            None => {
-                                return Poll::Ready(Err(RetryError::RequestBuilderCloneError));
-                            }
-                        },
-                        None => {
-                            return Poll::Ready(Err(RetryError::RequestBuilderNotAvailable));
-                        }
+                    let Some(template) = this.request.as_ref() else {
+                        return Poll::Ready(Err(RetryError::RequestBuilderNotAvailable));
+                    };
+                    let request = match this.tracker.next_request(this.config, template) {
+                        Ok(request) => request,
+                        Err(error) => return Poll::Ready(Err(error)),
                     };
 
                     // Prepare to transition to Requesting state
-                    let future = Box::pin(request.send());
+                    let future = Box::pin(this.tracker.instrument(this.client.execute(request)));
                     next_state = Some(RetryState::Requesting { future });
                     should_continue = true;
                     Poll::Pending // Will be overridden by continue
                 }
 
                 RetryStateProj::Requesting { future } => {
-                    match future.poll(cx) {
+                    let decision = match future.poll(cx) {
                         Poll::Ready(Ok(response)) => {
-                            // Classify the response error
-                            let error_type = (this.config.response_classifier)(&response);
-
-                            // Check if response indicates we should retry
-                            if (this.config.should_retry_response)(&response) {
-                                let strategy = this.config.get_effective_strategy(&error_type);
-
-                                if *this.attempts < strategy.max_retries {
-                                    *this.attempts += 1;
-                                    *this.current_error_type = Some(error_type.clone());
-
-                                    // Calculate delay using error-specific strategy
-                                    let delay = (strategy.backoff_fn)(
-                                        *this.attempts,
-                                        strategy.base_delay,
-                                        strategy.backoff_multiplier,
-                                        strategy.max_delay,
-                                    );
-
-                                    // Call retry callback if provided
-                                    if let Some(on_retry) = this.config.on_retry {
-                                        let retry_info = RetryAttempt {
-                                            attempt: *this.attempts,
-                                            max_attempts: strategy.max_retries + 1,
-                                            delay,
-                                            error: None,
-                                            response_status: Some(response.status().as_u16()),
-                                            error_type: error_type.clone(),
-                                        };
-                                        on_retry(&retry_info);
-                                    }
-
-                                    next_state = Some(RetryState::Sleeping {
-                                        sleep: sleep(delay),
-                                    });
-                                    should_continue = true;
-                                    Poll::Pending // Will be overridden by continue
-                                } else {
-                                    Poll::Ready(Ok(response))
-                                }
-                            } else {
-                                Poll::Ready(Ok(response))
-                            }
+                            this.tracker.on_response(this.config, response)
                         }
-                        Poll::Ready(Err(error)) => {
-                            // Classify the error
-                            let error_type = (this.config.error_classifier)(&error);
-
-                            // Check if this error should trigger a retry
-                            if !(this.config.should_retry)(&error) {
-                                Poll::Ready(Err(RetryError::NonRetryableError(error)))
-                            } else {
-                                let strategy = this.config.get_effective_strategy(&error_type);
-
-                                if *this.attempts >= strategy.max_retries {
-                                    // Call failure callback if provided
-                                    if let Some(on_failure) = this.config.on_failure {
-                                        let retry_info = RetryAttempt {
-                                            attempt: *this.attempts,
-                                            max_attempts: strategy.max_retries + 1,
-                                            delay: Duration::from_secs(0),
-                                            error: Some(error.to_string()),
-                                            response_status: None,
-                                            error_type: error_type.clone(),
-                                        };
-                                        on_failure(&retry_info);
-                                    }
-                                    Poll::Ready(Err(RetryError::RequestError(error)))
-                                } else {
-                                    *this.attempts += 1;
-                                    *this.current_error_type = Some(error_type.clone());
-
-                                    // Calculate delay using error-specific strategy
-                                    let delay = (strategy.backoff_fn)(
-                                        *this.attempts,
-                                        strategy.base_delay,
-                                        strategy.backoff_multiplier,
-                                        strategy.max_delay,
-                                    );
-
-                                    // Call retry callback if provided
-                                    if let Some(on_retry) = this.config.on_retry {
-                                        let retry_info = RetryAttempt {
-                                            attempt: *this.attempts,
-                                            max_attempts: strategy.max_retries + 1,
-                                            delay,
-                                            error: Some(error.to_string()),
-                                            response_status: None,
-                                            error_type: error_type.clone(),
-                                        };
-                                        on_retry(&retry_info);
-                                    }
-
-                                    next_state = Some(RetryState::Sleeping {
-                                        sleep: sleep(delay),
-                                    });
-                                    should_continue = true;
-                                    Poll::Pending // Will be overridden by continue
-                                }
-                            }
+                        Poll::Ready(Err(error)) => this.tracker.on_error(this.config, error),
+                        Poll::Pending => return Poll::Pending,
+                    };
+
+                    match decision {
+                        Decision::Retry(delay) => {
+                            next_state = Some(RetryState::Sleeping {
+                                sleep: sleep(delay),
+                            });
+                            should_continue = true;
+                            Poll::Pending // Will be overridden by continue
                         }
-                        Poll::Pending => Poll::Pending,
+                        Decision::Done(result) => Poll::Ready(result),
                     }
                 }
 
                 RetryStateProj::Sleeping { sleep } => {
                     match sleep.poll(cx) {
                         Poll::Ready(()) => {
-                            next_state = Some(RetryState::Ready);
+                            // Let the hook adjust the request before the next attempt
+                            next_state =
+                                match (&this.config.before_retry, this.tracker.last_retry()) {
+                                    (Some(hook), Some(attempt)) => match this.request.take() {
+                                        Some(request) => Some(RetryState::Hooking {
+                                            future: hook(attempt.clone(), request),
+                                        }),
+                                        None => {
+                                            return Poll::Ready(Err(
+                                                RetryError::RequestBuilderNotAvailable,
+                                            ));
+                                        }
+                                    },
+                                    _ => Some(RetryState::Ready),
+                                };
                             should_continue = true;
                             Poll::Pending // Will be overridden by continue
                         }
@@ -219,6 +162,17 @@ impl Future for RetryFuture {
                     }
                 }
 
+                RetryStateProj::Hooking { future } => match future.poll(cx) {
+                    Poll::Ready(Some(request)) => {
+                        *this.request = Some(request);
+                        next_state = Some(RetryState::Ready);
+                        should_continue = true;
+                        Poll::Pending // Will be overridden by continue
+                    }
+                    Poll::Ready(None) => Poll::Ready(Err(this.tracker.cancel())),
+                    Poll::Pending => Poll::Pending,
+                },
+
                 RetryStateProj::Done => {
                     panic!("RetryFuture polled after completion");
                 }
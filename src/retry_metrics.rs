@@ -0,0 +1,112 @@
+//! `metrics` facade instruments for retried requests, compiled to no-ops without the
+//! `metrics` feature
+//!
+//! Every metric is labelled with `host` and `method`:
+//!
+//! - `reqwest_retry_attempts_total` (counter): attempts sent, including the first one
+//! - `reqwest_retry_retries_total` (counter, plus `reason`): retries scheduled
+//! - `reqwest_retry_exhausted_total` (counter): operations that gave up while the outcome
+//!   was still retryable
+//! - `reqwest_retry_circuit_state` (gauge): circuit breaker state seen by the last
+//!   attempt, `0` closed, `1` half-open, `2` open
+//! - `reqwest_retry_delay_seconds` (histogram, plus `reason`): delays slept before retries
+//! - `reqwest_retry_attempt_duration_seconds` (histogram): time until each attempt's
+//!   outcome was known
+
+use crate::circuit_breaker::CircuitState;
+use crate::RetryReason;
+use reqwest::Request;
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+mod enabled {
+    use super::{CircuitState, Duration, Request, RetryReason};
+    use metrics::{counter, gauge, histogram, Label};
+
+    /// Host and method labels shared by every metric of one logical request
+    pub(crate) struct RetryMetrics {
+        labels: Vec<Label>,
+    }
+
+    impl RetryMetrics {
+        pub(crate) fn new(request: Option<&Request>) -> Self {
+            let (host, method) = request.map_or((String::new(), String::new()), |request| {
+                (
+                    request.url().host_str().unwrap_or_default().to_string(),
+                    request.method().to_string(),
+                )
+            });
+
+            Self {
+                labels: vec![Label::new("host", host), Label::new("method", method)],
+            }
+        }
+
+        /// Labels extended with the retry reason
+        fn with_reason(&self, reason: &RetryReason) -> Vec<Label> {
+            let mut labels = self.labels.clone();
+            labels.push(Label::new("reason", reason.name().to_string()));
+            labels
+        }
+
+        pub(crate) fn attempt_started(&self) {
+            counter!("reqwest_retry_attempts_total", self.labels.clone()).increment(1);
+        }
+
+        pub(crate) fn attempt_finished(&self, elapsed: Duration) {
+            histogram!(
+                "reqwest_retry_attempt_duration_seconds",
+                self.labels.clone()
+            )
+            .record(elapsed.as_secs_f64());
+        }
+
+        pub(crate) fn circuit_state(&self, state: CircuitState) {
+            let value = match state {
+                CircuitState::Closed => 0.0,
+                CircuitState::HalfOpen => 1.0,
+                CircuitState::Open => 2.0,
+            };
+            gauge!("reqwest_retry_circuit_state", self.labels.clone()).set(value);
+        }
+
+        pub(crate) fn retry(&self, reason: &RetryReason, delay: Duration) {
+            let labels = self.with_reason(reason);
+            counter!("reqwest_retry_retries_total", labels.clone()).increment(1);
+            histogram!("reqwest_retry_delay_seconds", labels).record(delay.as_secs_f64());
+        }
+
+        pub(crate) fn exhausted(&self) {
+            counter!("reqwest_retry_exhausted_total", self.labels.clone()).increment(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+mod disabled {
+    use super::{CircuitState, Duration, Request, RetryReason};
+
+    /// Stand-in for the instruments recorded with the `metrics` feature
+    pub(crate) struct RetryMetrics;
+
+    impl RetryMetrics {
+        pub(crate) fn new(_request: Option<&Request>) -> Self {
+            Self
+        }
+
+        pub(crate) fn attempt_started(&self) {}
+
+        pub(crate) fn attempt_finished(&self, _elapsed: Duration) {}
+
+        pub(crate) fn circuit_state(&self, _state: CircuitState) {}
+
+        pub(crate) fn retry(&self, _reason: &RetryReason, _delay: Duration) {}
+
+        pub(crate) fn exhausted(&self) {}
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) use disabled::RetryMetrics;
+#[cfg(feature = "metrics")]
+pub(crate) use enabled::RetryMetrics;
@@ -0,0 +1,147 @@
+//! `tracing` spans for retried requests, compiled to no-ops without the `tracing` feature
+
+use std::time::Duration;
+
+#[cfg(feature = "tracing")]
+mod enabled {
+    use super::Duration;
+    use crate::RetryReason;
+    use reqwest::Request;
+    use tracing::field::{debug, Empty};
+    use tracing::{Instrument, Span};
+
+    /// Span of an attempt set aside while another one is current
+    pub(crate) type AttemptSpan = Span;
+
+    /// Span for one logical request and a child span for its current attempt
+    pub(crate) struct RetrySpans {
+        operation: Span,
+        attempt: Span,
+        attempts: usize,
+    }
+
+    impl RetrySpans {
+        pub(crate) fn new(request: Option<&Request>) -> Self {
+            let operation = tracing::info_span!(
+                "retry",
+                method = Empty,
+                url = Empty,
+                attempts = Empty,
+                outcome = Empty,
+            );
+            if let Some(request) = request {
+                operation.record("method", request.method().as_str());
+                operation.record("url", request.url().as_str());
+            }
+
+            Self {
+                operation,
+                attempt: Span::none(),
+                attempts: 0,
+            }
+        }
+
+        /// Open the span of a new attempt
+        pub(crate) fn begin_attempt(&mut self) {
+            self.attempt = tracing::info_span!(
+                parent: &self.operation,
+                "attempt",
+                attempt = self.attempts,
+                reason = Empty,
+                status = Empty,
+                delay_ms = Empty,
+                outcome = Empty,
+            );
+            self.attempts += 1;
+        }
+
+        /// Set the span of the current attempt aside
+        pub(crate) fn take_attempt(&mut self) -> AttemptSpan {
+            std::mem::replace(&mut self.attempt, Span::none())
+        }
+
+        /// Make a span set aside by [`take_attempt`](Self::take_attempt) current again
+        pub(crate) fn resume_attempt(&mut self, span: AttemptSpan) {
+            self.attempt = span;
+        }
+
+        /// Record how the current attempt was classified
+        pub(crate) fn record_reason(&self, reason: &RetryReason, status: Option<u16>) {
+            self.attempt.record("reason", debug(reason));
+            if let Some(status) = status {
+                self.attempt.record("status", status);
+            }
+        }
+
+        /// Close the current attempt, and the operation with `outcome` if no retry follows
+        pub(crate) fn end_attempt(&mut self, retry: Option<Duration>, outcome: &'static str) {
+            match retry {
+                Some(delay) => {
+                    self.attempt.record("delay_ms", delay.as_millis() as u64);
+                    self.attempt.record("outcome", "retry");
+                }
+                None => {
+                    self.attempt.record("outcome", outcome);
+                    self.finish(outcome);
+                }
+            }
+            self.attempt = Span::none();
+        }
+
+        /// Record how the operation ended
+        pub(crate) fn finish(&self, outcome: &'static str) {
+            self.operation.record("attempts", self.attempts);
+            self.operation.record("outcome", outcome);
+        }
+
+        /// Run `future` inside the span of the current attempt
+        pub(crate) fn instrument<F: Future>(
+            &self,
+            future: F,
+        ) -> impl Future<Output = F::Output> + use<F> {
+            future.instrument(self.attempt.clone())
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod disabled {
+    use super::Duration;
+    use crate::RetryReason;
+    use reqwest::Request;
+
+    /// Stand-in for the span of an attempt set aside
+    pub(crate) struct AttemptSpan;
+
+    /// Stand-in for the spans recorded with the `tracing` feature
+    pub(crate) struct RetrySpans;
+
+    impl RetrySpans {
+        pub(crate) fn new(_request: Option<&Request>) -> Self {
+            Self
+        }
+
+        pub(crate) fn begin_attempt(&mut self) {}
+
+        pub(crate) fn take_attempt(&mut self) -> AttemptSpan {
+            AttemptSpan
+        }
+
+        pub(crate) fn resume_attempt(&mut self, _span: AttemptSpan) {}
+
+        pub(crate) fn record_reason(&self, _reason: &RetryReason, _status: Option<u16>) {}
+
+        pub(crate) fn end_attempt(&mut self, _retry: Option<Duration>, _outcome: &'static str) {}
+
+        pub(crate) fn finish(&self, _outcome: &'static str) {}
+
+        pub(crate) fn instrument<F: Future>(&self, future: F) -> F {
+            future
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use disabled::{AttemptSpan, RetrySpans};
+#[cfg(feature = "tracing")]
+pub(crate) use enabled::{AttemptSpan, RetrySpans};
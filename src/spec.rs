@@ -0,0 +1,278 @@
+use crate::backoff::{
+    Backoff, DecorrelatedJitter, EqualJitter, Exponential, ExponentialJitter, Fibonacci, Fixed,
+    FullJitter, Linear,
+};
+use crate::config::RetryConfig;
+use crate::{BackoffFactory, ErrorStrategy, Hook, RetryReason};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Backoff strategy selected by name in a [`RetryPolicySpec`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackoffKind {
+    /// [`Fixed`]
+    Fixed,
+    /// [`Linear`]
+    Linear,
+    /// [`Exponential`]
+    Exponential,
+    /// [`ExponentialJitter`]
+    ExponentialJitter,
+    /// [`Fibonacci`]
+    Fibonacci,
+    /// [`FullJitter`]
+    FullJitter,
+    /// [`EqualJitter`]
+    EqualJitter,
+    /// [`DecorrelatedJitter`]
+    DecorrelatedJitter,
+}
+
+impl BackoffKind {
+    /// Factory creating fresh state, and a fresh random source, for every request
+    fn factory(self) -> BackoffFactory {
+        fn boxed(backoff: impl Backoff + 'static) -> Box<dyn Backoff> {
+            Box::new(backoff)
+        }
+
+        let factory: Arc<dyn Fn() -> Box<dyn Backoff> + Send + Sync> = match self {
+            BackoffKind::Fixed => Arc::new(|| boxed(Fixed)),
+            BackoffKind::Linear => Arc::new(|| boxed(Linear)),
+            BackoffKind::Exponential => Arc::new(|| boxed(Exponential)),
+            BackoffKind::ExponentialJitter => Arc::new(|| boxed(ExponentialJitter)),
+            BackoffKind::Fibonacci => Arc::new(|| boxed(Fibonacci)),
+            BackoffKind::FullJitter => Arc::new(|| boxed(FullJitter::new())),
+            BackoffKind::EqualJitter => Arc::new(|| boxed(EqualJitter::new())),
+            BackoffKind::DecorrelatedJitter => Arc::new(|| boxed(DecorrelatedJitter::new())),
+        };
+        Hook::new(factory, format!("{self:?}"))
+    }
+}
+
+/// Serializable retry parameters, e.g. loaded from a TOML, YAML or JSON file
+///
+/// Unset fields keep the value of the config the spec is applied to. Delays are given in
+/// milliseconds; error strategies are keyed by [`RetryReason::name`] and status strategies
+/// by status code.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicySpec {
+    /// Maximum number of retry attempts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<usize>,
+    /// Base delay in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_delay_ms: Option<u64>,
+    /// Maximum delay between retries in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delay_ms: Option<u64>,
+    /// Multiplier for exponential backoff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backoff_multiplier: Option<f64>,
+    /// Named backoff strategy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backoff: Option<BackoffKind>,
+    /// Response statuses that trigger a retry, replacing the default predicate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retryable_statuses: Option<Vec<u16>>,
+    /// Strategies keyed by retry reason name, e.g. `rate_limit`, or `custom:<name>` for a
+    /// custom reason
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub error_strategies: BTreeMap<String, ErrorStrategySpec>,
+    /// Strategies keyed by response status, e.g. `503`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub status_strategies: BTreeMap<String, ErrorStrategySpec>,
+}
+
+/// Serializable [`ErrorStrategy`]; unset fields fall back to the config's defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorStrategySpec {
+    /// Maximum number of retry attempts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<usize>,
+    /// Base delay in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_delay_ms: Option<u64>,
+    /// Maximum delay between retries in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delay_ms: Option<u64>,
+    /// Multiplier for exponential backoff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backoff_multiplier: Option<f64>,
+    /// Named backoff strategy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backoff: Option<BackoffKind>,
+}
+
+/// Invalid value in a [`RetryPolicySpec`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid retry policy field `{field}`: {message}")]
+pub struct PolicySpecError {
+    /// Path of the offending field, e.g. `error_strategies.rate_limit.base_delay_ms`
+    pub field: String,
+    /// What is wrong with its value
+    pub message: String,
+}
+
+impl PolicySpecError {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl RetryPolicySpec {
+    /// Apply the spec on top of `config`, keeping its callbacks and other settings
+    pub fn apply(self, mut config: RetryConfig) -> Result<RetryConfig, PolicySpecError> {
+        if let Some(max_retries) = self.max_retries {
+            config.max_retries = max_retries;
+        }
+        if let Some(delay) = self.base_delay_ms {
+            config.base_delay = Duration::from_millis(delay);
+        }
+        if let Some(delay) = self.max_delay_ms {
+            config.max_delay = Duration::from_millis(delay);
+        }
+        if let Some(multiplier) = self.backoff_multiplier {
+            check_multiplier("backoff_multiplier", multiplier)?;
+            config.backoff_multiplier = multiplier;
+        }
+        if let Some(backoff) = self.backoff {
+            config.backoff = backoff.factory();
+        }
+        let delays_set = self.base_delay_ms.is_some() || self.max_delay_ms.is_some();
+        if delays_set && config.base_delay > config.max_delay {
+            let field = match self.base_delay_ms {
+                Some(_) => "base_delay_ms",
+                None => "max_delay_ms",
+            };
+            return Err(PolicySpecError::new(
+                field,
+                "base delay must not exceed the max delay",
+            ));
+        }
+
+        if let Some(statuses) = self.retryable_statuses {
+            let statuses = statuses
+                .iter()
+                .enumerate()
+                .map(|(index, code)| parse_status(&format!("retryable_statuses[{index}]"), *code))
+                .collect::<Result<Vec<_>, _>>()?;
+            let label = format!("retryable_statuses {statuses:?}");
+            config.should_retry_response = Hook::new(
+                Arc::new(move |response| statuses.contains(&response.status())),
+                label,
+            );
+        }
+
+        for (name, spec) in self.error_strategies {
+            let field = format!("error_strategies.{name}");
+            // Custom reasons are spelled out, so a misspelled built-in name is not one
+            let reason = match name.strip_prefix("custom:") {
+                Some(custom) => RetryReason::Custom(custom.to_string()),
+                None => match RetryReason::from_name(&name) {
+                    RetryReason::Custom(_) => {
+                        return Err(PolicySpecError::new(
+                            &field,
+                            "unknown retry reason, custom reasons need a `custom:` prefix",
+                        ));
+                    }
+                    reason => reason,
+                },
+            };
+            let strategy = spec.into_strategy(&field, &config)?;
+            config.error_strategies.insert(reason, strategy);
+        }
+
+        for (code, spec) in self.status_strategies {
+            let field = format!("status_strategies.{code}");
+            let status = code
+                .parse()
+                .map_err(|_| PolicySpecError::new(&field, "must be a numeric status code"))
+                .and_then(|code| parse_status(&field, code))?;
+            let strategy = spec.into_strategy(&field, &config)?;
+            config.status_strategies.insert(status, strategy);
+        }
+
+        Ok(config)
+    }
+}
+
+impl TryFrom<RetryPolicySpec> for RetryConfig {
+    type Error = PolicySpecError;
+
+    fn try_from(spec: RetryPolicySpec) -> Result<Self, Self::Error> {
+        spec.apply(RetryConfig::default())
+    }
+}
+
+impl ErrorStrategySpec {
+    /// Validate against the delays of `config` and build the strategy
+    fn into_strategy(
+        self,
+        field: &str,
+        config: &RetryConfig,
+    ) -> Result<ErrorStrategy, PolicySpecError> {
+        let mut strategy = ErrorStrategy::new();
+        if let Some(max_retries) = self.max_retries {
+            strategy = strategy.max_retries(max_retries);
+        }
+        if let Some(delay) = self.base_delay_ms {
+            strategy = strategy.base_delay(Duration::from_millis(delay));
+        }
+        if let Some(delay) = self.max_delay_ms {
+            strategy = strategy.max_delay(Duration::from_millis(delay));
+        }
+        if let Some(multiplier) = self.backoff_multiplier {
+            check_multiplier(&format!("{field}.backoff_multiplier"), multiplier)?;
+            strategy = strategy.backoff_multiplier(multiplier);
+        }
+        if let Some(backoff) = self.backoff {
+            strategy.backoff = Some(backoff.factory());
+        }
+
+        let delays_set = self.base_delay_ms.is_some() || self.max_delay_ms.is_some();
+        let base_delay = strategy.base_delay.unwrap_or(config.base_delay);
+        if delays_set && base_delay > strategy.max_delay.unwrap_or(config.max_delay) {
+            let field = match self.base_delay_ms {
+                Some(_) => format!("{field}.base_delay_ms"),
+                None => format!("{field}.max_delay_ms"),
+            };
+            return Err(PolicySpecError::new(
+                field,
+                "base delay must not exceed the max delay",
+            ));
+        }
+
+        Ok(strategy)
+    }
+}
+
+/// Reject multipliers that would shrink or break the delays
+fn check_multiplier(field: &str, multiplier: f64) -> Result<(), PolicySpecError> {
+    if multiplier.is_finite() && multiplier >= 1.0 {
+        Ok(())
+    } else {
+        Err(PolicySpecError::new(
+            field,
+            format!("{multiplier} is not a finite number of at least 1"),
+        ))
+    }
+}
+
+/// Parse an HTTP status code in the 100-599 range
+fn parse_status(field: &str, code: u16) -> Result<StatusCode, PolicySpecError> {
+    (100..600)
+        .contains(&code)
+        .then(|| StatusCode::from_u16(code).ok())
+        .flatten()
+        .ok_or_else(|| PolicySpecError::new(field, format!("{code} is not an HTTP status code")))
+}
@@ -1,6 +1,37 @@
-use crate::{backoff, default_backoff, ErrorStrategy, RetryConfig, RetryExt, RetryReason};
-use reqwest::Client;
-use std::time::Duration;
+use crate::backoff::Backoff;
+use crate::{
+    backoff, default_backoff, parse_retry_after, predicates, AttemptOutcome, ErrorStrategy,
+    RetryConfig, RetryError, RetryExt, RetryReason,
+};
+use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+use reqwest::{Client, StatusCode};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Serve the given raw HTTP responses in order (the last one repeats) on a local port
+async fn serve(responses: Vec<&'static str>) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let index = counter.fetch_add(1, Ordering::SeqCst);
+            let response = responses[index.min(responses.len() - 1)];
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket.write_all(response.as_bytes()).await;
+            let _ = socket.shutdown().await;
+        }
+    });
+
+    (url, hits)
+}
 
 #[tokio::test]
 async fn test_retry_config_builder() {
@@ -58,7 +89,7 @@ fn test_error_strategy_builder() {
 
     assert_eq!(strategy.max_retries, Some(10));
     assert_eq!(strategy.base_delay, Some(Duration::from_secs(1)));
-    assert!(strategy.backoff_fn.is_some());
+    assert!(strategy.backoff.is_some());
 }
 
 #[test]
@@ -76,12 +107,12 @@ fn test_effective_strategy() {
     );
 
     // Test that rate limit gets the specific strategy
-    let rate_limit_strategy = config.get_effective_strategy(&RetryReason::RateLimit);
+    let rate_limit_strategy = config.get_status_strategy(&RetryReason::RateLimit, None);
     assert_eq!(rate_limit_strategy.max_retries, 10);
     assert_eq!(rate_limit_strategy.base_delay, Duration::from_secs(1));
 
     // Test that other errors get the default strategy
-    let network_strategy = config.get_effective_strategy(&RetryReason::NetworkError);
+    let network_strategy = config.get_status_strategy(&RetryReason::NetworkError, None);
     assert_eq!(network_strategy.max_retries, 3);
     assert_eq!(network_strategy.base_delay, Duration::from_millis(100));
 }
@@ -199,3 +230,1786 @@ async fn integration_test() {
         response
     );
 }
+
+#[test]
+fn test_parse_retry_after() {
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+    let mut headers = HeaderMap::new();
+
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+    assert_eq!(
+        parse_retry_after(&headers, now),
+        Some(Duration::from_secs(120))
+    );
+
+    // Wed, 21 Oct 2015 07:28:00 GMT is 1_445_412_480 seconds after the epoch
+    headers.insert(
+        RETRY_AFTER,
+        HeaderValue::from_static("Wed, 21 Oct 2015 07:28:30 GMT"),
+    );
+    assert_eq!(
+        parse_retry_after(&headers, now),
+        Some(Duration::from_secs(30))
+    );
+
+    // Dates in the past allow an immediate retry
+    headers.insert(
+        RETRY_AFTER,
+        HeaderValue::from_static("Wed, 21 Oct 2015 07:00:00 GMT"),
+    );
+    assert_eq!(parse_retry_after(&headers, now), Some(Duration::ZERO));
+
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+    assert_eq!(parse_retry_after(&headers, now), None);
+}
+
+#[tokio::test]
+async fn test_retry_after_is_honored_within_ceiling() {
+    let delays = Arc::new(Mutex::new(Vec::new()));
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let config = || {
+        let delays = delays.clone();
+        let failures = failures.clone();
+        RetryConfig::new()
+            .base_delay(Duration::from_secs(5))
+            .max_retry_after(Duration::from_secs(60))
+            .on_retry(move |attempt| {
+                delays
+                    .lock()
+                    .unwrap()
+                    .push((attempt.delay, attempt.retry_after))
+            })
+            .on_failure(move |attempt| failures.lock().unwrap().push(attempt.retry_after))
+    };
+
+    // A hint within the ceiling replaces the backoff delay
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let response = Client::new()
+        .get(url)
+        .or_retry_with(config())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        *delays.lock().unwrap(),
+        vec![(Duration::ZERO, Some(Duration::ZERO))]
+    );
+
+    // A longer hint stops retrying rather than coming back early
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 3600\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let response = Client::new()
+        .get(url)
+        .or_retry_with(config())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 429);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    assert_eq!(delays.lock().unwrap().len(), 1);
+    assert_eq!(
+        *failures.lock().unwrap(),
+        vec![Some(Duration::from_secs(3600))]
+    );
+}
+
+#[tokio::test]
+async fn test_closure_hooks_capture_state() {
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+
+    let retries = Arc::new(AtomicUsize::new(0));
+    let counter = retries.clone();
+    let step = Duration::from_millis(1);
+
+    let response = Client::new()
+        .get(url)
+        .or_retry_with(
+            RetryConfig::new()
+                .should_retry_response(predicates::retry_on_status(&[StatusCode::NOT_FOUND]))
+                .backoff_fn(move |attempt, _, _, _| step * attempt as u32)
+                .on_retry(move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    assert_eq!(retries.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_backoff_structs_match_functions() {
+    let base_delay = Duration::from_millis(100);
+    let max_delay = Duration::from_secs(10);
+
+    for attempt in 1..6 {
+        assert_eq!(
+            backoff::Linear.next_delay(attempt, base_delay, 2.0, max_delay),
+            backoff::linear(attempt, base_delay, 2.0, max_delay)
+        );
+        assert_eq!(
+            backoff::Fibonacci.next_delay(attempt, base_delay, 2.0, max_delay),
+            backoff::fibonacci(attempt, base_delay, 2.0, max_delay)
+        );
+        assert_eq!(
+            backoff::Exponential.next_delay(attempt, base_delay, 2.0, max_delay),
+            default_backoff(attempt, base_delay, 2.0, max_delay)
+        );
+        assert_eq!(
+            backoff::FnBackoff(Arc::new(backoff::fixed))
+                .next_delay(attempt, base_delay, 2.0, max_delay),
+            backoff::fixed(attempt, base_delay, 2.0, max_delay)
+        );
+    }
+}
+
+/// Backoff that doubles its own previous delay, starting from the base delay
+#[derive(Clone, Default)]
+struct Doubling {
+    previous: Option<Duration>,
+}
+
+impl Backoff for Doubling {
+    fn next_delay(&mut self, _: usize, base_delay: Duration, _: f64, _: Duration) -> Duration {
+        let delay = self.previous.map_or(base_delay, |previous| previous * 2);
+        self.previous = Some(delay);
+        delay
+    }
+}
+
+#[tokio::test]
+async fn test_stateful_backoff_is_per_request() {
+    let delays = Arc::new(Mutex::new(Vec::new()));
+    let recorded = delays.clone();
+    let config = || {
+        let recorded = recorded.clone();
+        RetryConfig::new()
+            .max_retries(2)
+            .base_delay(Duration::from_millis(1))
+            .backoff(Doubling::default())
+            .on_retry(move |attempt| recorded.lock().unwrap().push(attempt.delay))
+    };
+
+    for _ in 0..2 {
+        let (url, _) = serve(vec![
+            "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n",
+        ])
+        .await;
+        let response = Client::new()
+            .get(url)
+            .or_retry_with(config())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 500);
+    }
+
+    let ms = Duration::from_millis;
+    assert_eq!(*delays.lock().unwrap(), vec![ms(1), ms(2), ms(1), ms(2)]);
+}
+
+#[test]
+fn test_seeded_jitter_is_reproducible() {
+    let base_delay = Duration::from_millis(100);
+    let max_delay = Duration::from_secs(10);
+    let schedule = |backoff: &mut dyn Backoff| -> Vec<Duration> {
+        (1..8)
+            .map(|attempt| backoff.next_delay(attempt, base_delay, 2.0, max_delay))
+            .collect()
+    };
+
+    assert_eq!(
+        schedule(&mut backoff::FullJitter::seeded(7)),
+        schedule(&mut backoff::FullJitter::seeded(7))
+    );
+    assert_eq!(
+        schedule(&mut backoff::EqualJitter::seeded(7)),
+        schedule(&mut backoff::EqualJitter::seeded(7))
+    );
+    assert_eq!(
+        schedule(&mut backoff::DecorrelatedJitter::seeded(7)),
+        schedule(&mut backoff::DecorrelatedJitter::seeded(7))
+    );
+    assert_ne!(
+        schedule(&mut backoff::FullJitter::seeded(7)),
+        schedule(&mut backoff::FullJitter::seeded(8))
+    );
+}
+
+#[test]
+fn test_jitter_bounds() {
+    let base_delay = Duration::from_millis(100);
+    let max_delay = Duration::from_secs(2);
+    let mut full = backoff::FullJitter::new();
+    let mut equal = backoff::EqualJitter::new();
+    let mut decorrelated = backoff::DecorrelatedJitter::new();
+    let mut previous = base_delay;
+
+    for attempt in 1..10 {
+        let ceiling = default_backoff(attempt, base_delay, 2.0, max_delay);
+        assert!(full.next_delay(attempt, base_delay, 2.0, max_delay) <= ceiling);
+
+        let delay = equal.next_delay(attempt, base_delay, 2.0, max_delay);
+        assert!(delay >= ceiling / 2 && delay <= ceiling);
+
+        let delay = decorrelated.next_delay(attempt, base_delay, 2.0, max_delay);
+        assert!(delay >= base_delay && delay <= (previous * 3).min(max_delay));
+        previous = delay;
+    }
+
+    // Unseeded clones draw independent schedules
+    let prototype = backoff::FullJitter::new();
+    let draws: Vec<Duration> = (0..16)
+        .map(|_| prototype.clone().next_delay(10, base_delay, 2.0, max_delay))
+        .collect();
+    assert!(draws.iter().any(|delay| *delay != draws[0]));
+}
+
+/// Serve the given raw HTTP responses like [`serve`], recording every request received
+async fn serve_recording(responses: Vec<&'static str>) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let read = socket.read(&mut buf).await.unwrap_or(0);
+            let index = {
+                let mut requests = recorded.lock().unwrap();
+                requests.push(String::from_utf8_lossy(&buf[..read]).into_owned());
+                requests.len() - 1
+            };
+            let response = responses[index.min(responses.len() - 1)];
+            let _ = socket.write_all(response.as_bytes()).await;
+            let _ = socket.shutdown().await;
+        }
+    });
+
+    (url, requests)
+}
+
+/// Address of a local port that refuses connections
+async fn refused_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    url
+}
+
+#[tokio::test]
+async fn test_exhaustion_error_carries_history() {
+    let error = Client::new()
+        .get(refused_url().await)
+        .or_retry_with(
+            RetryConfig::new()
+                .max_retries(2)
+                .base_delay(Duration::from_millis(1))
+                .backoff(backoff::Linear),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, RetryError::RequestError { .. }));
+    let history = error.history().unwrap();
+    assert_eq!(history.attempts.len(), 3);
+
+    let delays: Vec<Duration> = history.attempts.iter().map(|a| a.delay).collect();
+    let ms = Duration::from_millis;
+    assert_eq!(delays, vec![ms(1), ms(2), ms(0)]);
+
+    for (index, record) in history.attempts.iter().enumerate() {
+        assert_eq!(record.attempt, index);
+        assert_eq!(record.reason, RetryReason::NetworkError);
+        assert!(matches!(record.outcome, AttemptOutcome::Error(_)));
+    }
+    assert!(history.attempts[0].timestamp <= history.attempts[2].timestamp);
+    assert!(history.total_elapsed >= ms(3));
+}
+
+#[tokio::test]
+async fn test_response_exhaustion() {
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let config = |error_on_exhausted_response: bool| {
+        let recorded = failures.clone();
+        RetryConfig::new()
+            .max_retries(1)
+            .base_delay(Duration::from_millis(1))
+            .error_on_exhausted_response(error_on_exhausted_response)
+            .on_failure(move |attempt| recorded.lock().unwrap().push(attempt.response_status))
+    };
+    let unavailable = "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 4\r\n\r\ndown";
+
+    // Default mode still resolves to the last response
+    let (url, _) = serve(vec![unavailable]).await;
+    let response = Client::new()
+        .get(url)
+        .or_retry_with(config(false))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+
+    // Error mode hands back the response inside the error
+    let (url, hits) = serve(vec![unavailable]).await;
+    let error = Client::new()
+        .get(url)
+        .or_retry_with(config(true))
+        .await
+        .unwrap_err();
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(error.history().unwrap().attempts.len(), 2);
+    let response = error.into_response().unwrap();
+    assert_eq!(response.text().await.unwrap(), "down");
+
+    assert_eq!(*failures.lock().unwrap(), vec![Some(503), Some(503)]);
+}
+
+/// Address of a local server that accepts connections but never answers
+async fn hanging_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        let mut open = Vec::new();
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            open.push(socket);
+        }
+    });
+
+    url
+}
+
+#[tokio::test]
+async fn test_attempt_timeout_retries_each_attempt() {
+    let error = Client::new()
+        .get(hanging_url().await)
+        .or_retry_with(
+            RetryConfig::new()
+                .max_retries(2)
+                .base_delay(Duration::from_millis(1))
+                .attempt_timeout(Duration::from_millis(50)),
+        )
+        .await
+        .unwrap_err();
+
+    let history = error.history().unwrap();
+    assert!(matches!(error, RetryError::RequestError { ref source, .. } if source.is_timeout()));
+    assert_eq!(history.attempts.len(), 3);
+    assert!(
+        history
+            .attempts
+            .iter()
+            .all(|a| a.elapsed >= Duration::from_millis(50))
+    );
+
+    // A shorter timeout set on the request builder is kept
+    let started = std::time::Instant::now();
+    let error = Client::new()
+        .get(hanging_url().await)
+        .timeout(Duration::from_millis(50))
+        .or_retry_with(
+            RetryConfig::new()
+                .max_retries(0)
+                .attempt_timeout(Duration::from_secs(2)),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::RequestError { ref source, .. } if source.is_timeout()));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_deadline_stops_retrying() {
+    let error = Client::new()
+        .get(hanging_url().await)
+        .or_retry_with(
+            RetryConfig::new()
+                .max_retries(10)
+                .base_delay(Duration::from_millis(20))
+                .backoff(backoff::Fixed)
+                .attempt_timeout(Duration::from_millis(100))
+                .deadline(Duration::from_millis(250)),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, RetryError::DeadlineExceeded { .. }));
+    let history = error.history().unwrap();
+    // The last attempt is cut short by the time left before the deadline
+    assert_eq!(history.attempts.len(), 3);
+    assert!(history.total_elapsed < Duration::from_millis(300));
+
+    // Retryable responses stop at the deadline with the same error
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let error = Client::new()
+        .get(url)
+        .or_retry_with(
+            RetryConfig::new()
+                .max_retries(10)
+                .base_delay(Duration::from_millis(100))
+                .backoff(backoff::Fixed)
+                .deadline(Duration::from_millis(150)),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::DeadlineExceeded { .. }));
+    assert_eq!(error.history().unwrap().attempts.len(), 2);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_deadline_check_does_not_overflow() {
+    let config = RetryConfig::new().deadline(Duration::from_secs(10));
+    let started = std::time::Instant::now()
+        .checked_sub(Duration::from_secs(2))
+        .unwrap();
+
+    // A server-controlled delay near the maximum must not panic
+    assert!(config.exceeds_deadline(Some(started), Duration::MAX));
+    assert!(!config.exceeds_deadline(Some(started), Duration::from_secs(1)));
+}
+
+#[cfg(feature = "middleware")]
+#[tokio::test]
+async fn test_middleware_retries() {
+    use crate::RetryMiddleware;
+    use reqwest_middleware::ClientBuilder;
+
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 502 Bad Gateway\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let client = ClientBuilder::new(Client::new())
+        .with(RetryMiddleware::new(
+            RetryConfig::new().base_delay(Duration::from_millis(1)),
+        ))
+        .build();
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // Exhaustion surfaces the retry error, including its history
+    let client = ClientBuilder::new(Client::new())
+        .with(RetryMiddleware::new(
+            RetryConfig::new()
+                .max_retries(1)
+                .base_delay(Duration::from_millis(1)),
+        ))
+        .build();
+    let error = client.get(refused_url().await).send().await.unwrap_err();
+    let reqwest_middleware::Error::Middleware(error) = error else {
+        panic!("Expected a middleware error, got: {:?}", error);
+    };
+    let error = error.downcast_ref::<RetryError>().unwrap();
+    assert_eq!(error.history().unwrap().attempts.len(), 2);
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn test_tower_layer_retries() {
+    use crate::RetryLayer;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let mut service = RetryLayer::new(RetryConfig::new().base_delay(Duration::from_millis(1)))
+        .layer(Client::new());
+
+    let request = reqwest::Request::new(reqwest::Method::GET, url.parse().unwrap());
+    std::future::poll_fn(|cx| service.poll_ready(cx))
+        .await
+        .unwrap();
+    let response = service.call(request).await.unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn test_tower_layer_accepts_boxed_errors() {
+    use crate::RetryLayer;
+    use tower::timeout::error::Elapsed;
+    use tower::{ServiceBuilder, ServiceExt};
+
+    let service = |url: &str| {
+        let service = ServiceBuilder::new()
+            .layer(RetryLayer::new(
+                RetryConfig::new()
+                    .max_retries(2)
+                    .base_delay(Duration::from_millis(1)),
+            ))
+            .timeout(Duration::from_millis(50))
+            .service(Client::new());
+        let request = reqwest::Request::new(reqwest::Method::GET, url.parse().unwrap());
+        service.oneshot(request)
+    };
+
+    // Boxed reqwest errors are still classified and retried
+    let error = service(&refused_url().await).await.unwrap_err();
+    let error = error.downcast::<RetryError>().unwrap();
+    assert!(
+        matches!(*error, RetryError::RequestError { ref history, .. } if history.attempts.len() == 3)
+    );
+
+    // Other errors end the request without a retry
+    let error = service(&hanging_url().await).await.unwrap_err();
+    assert!(error.is::<Elapsed>());
+}
+
+#[tokio::test]
+async fn test_non_idempotent_methods_are_not_retried_by_default() {
+    let failing = "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n";
+    let config = || RetryConfig::new().base_delay(Duration::from_millis(1));
+
+    // POST is sent once
+    let (url, hits) = serve(vec![failing]).await;
+    let response = Client::new()
+        .post(url)
+        .or_retry_with(config())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 500);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // ...unless the method is explicitly allowed
+    let (url, hits) = serve(vec![failing]).await;
+    Client::new()
+        .post(url)
+        .or_retry_with(config().allow_method(reqwest::Method::POST))
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 4);
+
+    // ...or it carries an Idempotency-Key and that opt-in is enabled
+    let (url, hits) = serve(vec![failing]).await;
+    Client::new()
+        .post(&url)
+        .header(crate::IDEMPOTENCY_KEY, "order-42")
+        .or_retry_with(config())
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    Client::new()
+        .post(&url)
+        .header(crate::IDEMPOTENCY_KEY, "order-42")
+        .or_retry_with(config().retry_with_idempotency_key(true))
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 5);
+}
+
+/// Extract a header value from a raw recorded request
+fn recorded_header(request: &str, name: &str) -> Option<String> {
+    request.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.eq_ignore_ascii_case(name)
+            .then(|| value.trim().to_string())
+    })
+}
+
+#[tokio::test]
+async fn test_generated_idempotency_key_is_reused() {
+    let (url, requests) = serve_recording(vec![
+        "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 201 Created\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let config = RetryConfig::new()
+        .base_delay(Duration::from_millis(1))
+        .generate_idempotency_key();
+
+    let response = Client::new()
+        .post(&url)
+        .or_retry_with(config)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let keys: Vec<String> = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|request| recorded_header(request, "idempotency-key").unwrap())
+        .collect();
+    assert_eq!(keys.len(), 3);
+    assert!(keys.iter().all(|key| *key == keys[0]));
+    assert!(uuid::Uuid::parse_str(&keys[0]).is_ok());
+
+    // A caller-provided key is left untouched
+    let config = RetryConfig::new()
+        .max_retries(0)
+        .idempotency_key_generator(|| "generated".to_string());
+    Client::new()
+        .post(&url)
+        .header(crate::IDEMPOTENCY_KEY, "mine")
+        .or_retry_with(config)
+        .await
+        .unwrap();
+    let last = requests.lock().unwrap().last().cloned().unwrap();
+    assert_eq!(
+        recorded_header(&last, "idempotency-key").as_deref(),
+        Some("mine")
+    );
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_opens_and_recovers() {
+    use crate::{CircuitBreaker, CircuitState};
+    use reqwest::Body;
+
+    let failing = "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n";
+    let (url, hits) = serve(vec![
+        failing,
+        failing,
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let breaker = Arc::new(
+        CircuitBreaker::new()
+            .minimum_requests(2)
+            .failure_rate_threshold(0.5)
+            .cool_down(Duration::from_millis(200)),
+    );
+    let client = Client::new();
+    let send = || {
+        client.get(&url).or_retry_with(
+            RetryConfig::new()
+                .max_retries(0)
+                .circuit_breaker(breaker.clone()),
+        )
+    };
+
+    // Two failures open the circuit
+    assert_eq!(send().await.unwrap().status(), 500);
+    assert_eq!(breaker.state(), CircuitState::Closed);
+    assert_eq!(send().await.unwrap().status(), 500);
+    assert_eq!(breaker.state(), CircuitState::Open);
+
+    // Further requests fail fast without reaching the server
+    assert!(matches!(send().await, Err(RetryError::CircuitOpen { .. })));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // After the cool-down a successful probe closes it again
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+    // A request that cannot be cloned fails without taking the probe slot
+    let result = client
+        .post(&url)
+        .body(Body::wrap(String::from("payload")))
+        .or_retry_with(RetryConfig::new().circuit_breaker(breaker.clone()))
+        .await;
+    assert!(matches!(result, Err(RetryError::RequestBuilderCloneError)));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    assert_eq!(send().await.unwrap().status(), 200);
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
+
+#[tokio::test]
+async fn test_retry_budget_suppresses_retries_once_spent() {
+    use crate::RetryBudget;
+
+    let budget = Arc::new(
+        RetryBudget::new()
+            .max_tokens(2.0)
+            .retry_ratio(0.0)
+            .min_retries_per_second(0.0),
+    );
+    let reported = Arc::new(AtomicBool::new(false));
+    let flag = reported.clone();
+
+    let error = Client::new()
+        .get(refused_url().await)
+        .or_retry_with(
+            RetryConfig::new()
+                .max_retries(5)
+                .base_delay(Duration::from_millis(1))
+                .retry_budget(budget.clone())
+                .on_failure(move |info| flag.store(info.budget_exhausted, Ordering::SeqCst)),
+        )
+        .await
+        .unwrap_err();
+
+    // Two retries were paid for, the third was suppressed
+    assert!(matches!(error, RetryError::BudgetExhausted { .. }));
+    assert_eq!(error.history().unwrap().attempts.len(), 3);
+    assert!(reported.load(Ordering::SeqCst));
+    assert_eq!(budget.available(), 0.0);
+
+    // A suppressed retry of a retryable response reports the budget as well
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 4\r\n\r\ndown",
+    ])
+    .await;
+    let budget = Arc::new(
+        RetryBudget::new()
+            .max_tokens(1.0)
+            .retry_ratio(0.0)
+            .min_retries_per_second(0.0),
+    );
+    let error = Client::new()
+        .get(url)
+        .or_retry_with(
+            RetryConfig::new()
+                .max_retries(5)
+                .base_delay(Duration::from_millis(1))
+                .retry_budget(budget),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::ResponseBudgetExhausted { .. }));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(error.history().unwrap().attempts.len(), 2);
+    assert_eq!(error.into_response().unwrap().status(), 503);
+}
+
+#[tokio::test]
+async fn test_hedged_request_takes_first_response() {
+    use crate::HedgeConfig;
+
+    // The first connection never answers, later ones answer immediately
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    tokio::spawn(async move {
+        let mut stalled = Vec::new();
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                stalled.push(socket);
+                continue;
+            }
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await;
+        }
+    });
+
+    let client = Client::new();
+    let hedge = HedgeConfig::new().delay(Duration::from_millis(50));
+    let started = std::time::Instant::now();
+    let response = client
+        .get(&url)
+        .or_hedge_with(RetryConfig::new().max_retries(1), hedge.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // Non-idempotent requests are never hedged and wait on the single attempt
+    let error = client
+        .post(hanging_url().await)
+        .timeout(Duration::from_millis(200))
+        .or_hedge(hedge)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::NonRetryableError(_)));
+}
+
+#[tokio::test]
+async fn test_hedged_attempts_follow_retry_rules() {
+    use crate::{CircuitBreaker, HedgeConfig};
+
+    // A failed attempt is retried after its backoff, not replaced right away
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let retries = Arc::new(AtomicUsize::new(0));
+    let counter = retries.clone();
+    let started = std::time::Instant::now();
+    let response = Client::new()
+        .get(&url)
+        .or_hedge_with(
+            RetryConfig::new()
+                .backoff(backoff::Fixed)
+                .base_delay(Duration::from_millis(100))
+                .on_retry(move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }),
+            HedgeConfig::new().delay(Duration::from_secs(5)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(retries.load(Ordering::SeqCst), 1);
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    // An open circuit stops the request before any copy is sent
+    let (url, hits) = serve(vec!["HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n"]).await;
+    let breaker = Arc::new(CircuitBreaker::new().minimum_requests(1));
+    breaker.record(true);
+    let error = Client::new()
+        .get(&url)
+        .or_hedge_with(
+            RetryConfig::new().circuit_breaker(breaker),
+            HedgeConfig::new(),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::CircuitOpen { .. }));
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+/// Serve connections by script (the last entry repeats) on a local port: `None` never
+/// answers, `Some` answers with the raw response after the delay
+async fn serve_scripted(
+    script: Vec<Option<(Duration, &'static str)>>,
+) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let index = counter.fetch_add(1, Ordering::SeqCst);
+            let step = script[index.min(script.len() - 1)];
+            tokio::spawn(async move {
+                let Some((delay, response)) = step else {
+                    return std::future::pending::<()>().await;
+                };
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                tokio::time::sleep(delay).await;
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            });
+        }
+    });
+
+    (url, hits)
+}
+
+#[tokio::test]
+async fn test_hedged_copies_count_as_retries() {
+    use crate::HedgeConfig;
+
+    // The first copy stalls and the hedge fails: with one retry, that was the last copy
+    let unavailable = "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n";
+    let (url, hits) = serve_scripted(vec![None, Some((Duration::ZERO, unavailable))]).await;
+    let response = Client::new()
+        .get(&url)
+        .or_hedge_with(
+            RetryConfig::new()
+                .max_retries(1)
+                .backoff(backoff::Fixed)
+                .base_delay(Duration::from_millis(10)),
+            HedgeConfig::new().delay(Duration::from_millis(20)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_hedged_retry_waits_for_running_hook() {
+    use crate::HedgeConfig;
+
+    // A second retry comes due while the hook of the first one still holds the request
+    let unavailable = "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n";
+    let (url, hits) = serve_scripted(vec![
+        None,
+        Some((Duration::ZERO, unavailable)),
+        Some((Duration::from_millis(100), unavailable)),
+        Some((
+            Duration::ZERO,
+            "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+        )),
+    ])
+    .await;
+    let response = Client::new()
+        .get(&url)
+        .or_hedge_with(
+            RetryConfig::new()
+                .max_retries(5)
+                .backoff(backoff::Fixed)
+                .base_delay(Duration::from_millis(40))
+                .before_retry(|_, request| async move {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    Some(request)
+                }),
+            HedgeConfig::new().delay(Duration::from_millis(30)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(hits.load(Ordering::SeqCst) >= 4);
+}
+
+#[test]
+fn test_hedged_request_is_created_outside_a_runtime() {
+    use crate::HedgeConfig;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let (url, hits) = runtime.block_on(serve(vec!["HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n"]));
+
+    let future = Client::new().get(&url).or_hedge(HedgeConfig::new());
+    let response = runtime.block_on(future).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn test_tracing_spans_per_operation_and_attempt() {
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// Layer collecting `span.field=value` for every recorded span field
+    struct Fields(Arc<Mutex<Vec<String>>>);
+
+    struct Collect<'a>(&'a str, &'a Mutex<Vec<String>>);
+
+    impl Visit for Collect<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            let entry = format!("{}.{}={:?}", self.0, field.name(), value);
+            self.1.lock().unwrap().push(entry.replace('"', ""));
+        }
+    }
+
+    impl<S: tracing::Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for Fields {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+            attrs.record(&mut Collect(attrs.metadata().name(), &self.0));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            let name = ctx.span(id).unwrap().name();
+            values.record(&mut Collect(name, &self.0));
+        }
+    }
+
+    let fields = Arc::new(Mutex::new(Vec::new()));
+    let subscriber = tracing_subscriber::registry().with(Fields(fields.clone()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (url, _) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let response = Client::new()
+        .get(&url)
+        .or_retry_with(RetryConfig::new().base_delay(Duration::from_millis(1)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let fields = fields.lock().unwrap();
+    for expected in [
+        "retry.method=GET".to_string(),
+        format!("retry.url={url}/"),
+        "retry.attempts=2".to_string(),
+        "retry.outcome=completed".to_string(),
+        "attempt.attempt=0".to_string(),
+        "attempt.reason=ServerError".to_string(),
+        "attempt.status=503".to_string(),
+        "attempt.outcome=retry".to_string(),
+        "attempt.attempt=1".to_string(),
+        "attempt.status=200".to_string(),
+        "attempt.outcome=completed".to_string(),
+    ] {
+        assert!(
+            fields.contains(&expected),
+            "missing {expected} in {fields:?}"
+        );
+    }
+    assert!(
+        fields
+            .iter()
+            .any(|field| field.starts_with("attempt.delay_ms="))
+    );
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_metrics_recorded_per_attempt() {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use std::collections::HashMap;
+
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    metrics::with_local_recorder(&recorder, || {
+        runtime.block_on(async {
+            let (url, _) = serve(vec![
+                "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+                "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+            ])
+            .await;
+            let response = Client::new()
+                .get(&url)
+                .or_retry_with(RetryConfig::new().base_delay(Duration::from_millis(1)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+        })
+    });
+
+    // Key every metric by its name and labels
+    let metrics: HashMap<String, DebugValue> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let labels: Vec<String> = key
+                .key()
+                .labels()
+                .map(|label| format!("{}={}", label.key(), label.value()))
+                .collect();
+            (
+                format!("{}{{{}}}", key.key().name(), labels.join(",")),
+                value,
+            )
+        })
+        .collect();
+
+    let labels = "host=127.0.0.1,method=GET";
+    assert_eq!(
+        metrics[&format!("reqwest_retry_attempts_total{{{labels}}}")],
+        DebugValue::Counter(2)
+    );
+    assert_eq!(
+        metrics[&format!("reqwest_retry_retries_total{{{labels},reason=server_error}}")],
+        DebugValue::Counter(1)
+    );
+    assert!(matches!(
+        &metrics[&format!("reqwest_retry_delay_seconds{{{labels},reason=server_error}}")],
+        DebugValue::Histogram(delays) if delays.len() == 1
+    ));
+    assert!(matches!(
+        &metrics[&format!("reqwest_retry_attempt_duration_seconds{{{labels}}}")],
+        DebugValue::Histogram(latencies) if latencies.len() == 2
+    ));
+    assert!(
+        !metrics
+            .keys()
+            .any(|key| key.starts_with("reqwest_retry_exhausted_total"))
+    );
+}
+
+#[tokio::test]
+async fn test_before_retry_hook_refreshes_request() {
+    use reqwest::header::AUTHORIZATION;
+
+    let (url, requests) = serve_recording(vec![
+        "HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let config = RetryConfig::new()
+        .base_delay(Duration::from_millis(1))
+        .should_retry_response(|response| response.status() == StatusCode::UNAUTHORIZED)
+        .before_retry(|attempt, mut request| async move {
+            // Simulate refreshing an expired token
+            tokio::task::yield_now().await;
+            if attempt.response_status == Some(401) {
+                let token = HeaderValue::from_static("Bearer fresh");
+                request.headers_mut().insert(AUTHORIZATION, token);
+            }
+            Some(request)
+        });
+
+    let response = Client::new()
+        .get(&url)
+        .bearer_auth("expired")
+        .or_retry_with(config)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(
+        recorded_header(&requests[0], "authorization").as_deref(),
+        Some("Bearer expired")
+    );
+    assert_eq!(
+        recorded_header(&requests[1], "authorization").as_deref(),
+        Some("Bearer fresh")
+    );
+}
+
+#[tokio::test]
+async fn test_before_retry_hook_can_stop_retrying() {
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let error = Client::new()
+        .get(&url)
+        .or_retry_with(
+            RetryConfig::new()
+                .base_delay(Duration::from_millis(1))
+                .before_retry(|_, _| async { None }),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, RetryError::RetryCancelled { .. }));
+    assert_eq!(error.history().unwrap().attempts.len(), 1);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_failover_moves_to_next_endpoint() {
+    use crate::{Failover, FailoverMode};
+
+    let (primary, primary_hits) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let (secondary, secondary_hits) =
+        serve(vec!["HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n"]).await;
+    let failover = Arc::new(
+        Failover::new([primary.parse().unwrap(), secondary.parse().unwrap()])
+            .mode(FailoverMode::Ordered),
+    );
+    let endpoints = Arc::new(Mutex::new(Vec::new()));
+    let seen = endpoints.clone();
+    let config = || {
+        let seen = seen.clone();
+        RetryConfig::new()
+            .base_delay(Duration::from_millis(1))
+            .failover(failover.clone())
+            .on_retry(move |attempt| seen.lock().unwrap().push(attempt.endpoint.clone()))
+    };
+
+    // The path and query are kept while the host is replaced
+    let response = Client::new()
+        .get(format!("{primary}/items?page=2"))
+        .or_retry_with(config())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.url().as_str(), format!("{secondary}/items?page=2"));
+    assert_eq!(
+        *endpoints.lock().unwrap(),
+        vec![Some(format!("{secondary}/"))]
+    );
+
+    // The primary just failed, so the next request starts on the secondary
+    let response = Client::new()
+        .get(format!("{primary}/items"))
+        .or_retry_with(config())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_hits.load(Ordering::SeqCst), 2);
+
+    // Reading the body successfully marks the endpoint healthy again
+    let (flaky, _) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok",
+    ])
+    .await;
+    let failover = Arc::new(Failover::new([
+        flaky.parse().unwrap(),
+        refused_url().await.parse().unwrap(),
+    ]));
+    let body = Client::new()
+        .get(&flaky)
+        .or_retry_bytes_with(
+            RetryConfig::new()
+                .base_delay(Duration::from_millis(1))
+                .failover(failover.clone()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(body, "ok");
+    // Once the other endpoint recovers too, the first one is preferred again
+    failover.record(1, false);
+    assert_eq!(failover.select(), Some(0));
+
+    // An endpoint's own path is kept in front of the request path
+    let failover = Failover::new([
+        "https://eu.example.com/api/v2/".parse().unwrap(),
+        "https://us.example.com/api".parse().unwrap(),
+    ]);
+    for (index, expected) in [
+        (0, "https://eu.example.com/api/v2/orders?x=1"),
+        (1, "https://us.example.com/api/orders?x=1"),
+    ] {
+        let mut url = "https://primary.example.com/orders?x=1".parse().unwrap();
+        failover.apply(index, &mut url);
+        assert_eq!(url.as_str(), expected);
+    }
+}
+
+#[tokio::test]
+async fn test_streaming_body_is_buffered_and_replayed() {
+    use reqwest::Body;
+
+    let responses = vec![
+        "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ];
+    let config = || RetryConfig::new().base_delay(Duration::from_millis(1));
+
+    // Without buffering a streaming body cannot be cloned for the retry
+    let (url, _) = serve(responses.clone()).await;
+    let error = Client::new()
+        .put(&url)
+        .body(Body::wrap(String::from("payload")))
+        .or_retry_with(config())
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::RequestBuilderCloneError));
+
+    // With buffering the body is sent again in full
+    let (url, requests) = serve_recording(responses.clone()).await;
+    let response = Client::new()
+        .put(&url)
+        .body(Body::wrap(String::from("payload")))
+        .or_retry_with(config().buffer_body(1024))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|request| request.contains("payload"))
+    );
+
+    // A body beyond the limit is sent once, and its response is kept instead of retrying
+    let (url, hits) = serve(responses).await;
+    let retried = Arc::new(AtomicBool::new(false));
+    let flag = retried.clone();
+    let response = Client::new()
+        .put(&url)
+        .body(Body::wrap(String::from("payload")))
+        .or_retry_with(
+            config()
+                .buffer_body(4)
+                .on_retry(move |_| flag.store(true, Ordering::SeqCst)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 500);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    assert!(!retried.load(Ordering::SeqCst));
+
+    // ...and so is its error, with the history
+    let (url, hits) = serve(vec![""]).await;
+    let error = Client::new()
+        .put(&url)
+        .body(Body::wrap(String::from("payload")))
+        .or_retry_with(config().buffer_body(4))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        RetryError::RequestError { ref history, .. } if history.attempts.len() == 1
+    ));
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_body_factory_produces_body_per_attempt() {
+    use reqwest::Body;
+
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let bodies = Arc::new(AtomicUsize::new(0));
+    let produced = bodies.clone();
+
+    let response = Client::new()
+        .put(&url)
+        .or_retry_with(RetryConfig::new().base_delay(Duration::from_millis(1)))
+        .body_factory(move || {
+            produced.fetch_add(1, Ordering::SeqCst);
+            Body::wrap(String::from("payload"))
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(bodies.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_generic_retry_classifies_errors() {
+    use crate::{retry, OperationError};
+    use std::io::{Error, ErrorKind};
+
+    let retries = Arc::new(AtomicUsize::new(0));
+    let counter = retries.clone();
+    let config = RetryConfig::new()
+        .max_retries(3)
+        .base_delay(Duration::from_millis(1))
+        .on_retry(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+    // Network-like errors are retried until the operation succeeds
+    let calls = AtomicUsize::new(0);
+    let value = retry(&config, || async {
+        match calls.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => Err(Error::from(ErrorKind::ConnectionReset)),
+            _ => Ok(42),
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(value, 42);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(retries.load(Ordering::SeqCst), 2);
+
+    // Other errors are final
+    let calls = AtomicUsize::new(0);
+    let error = retry(&config, || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(Error::from(ErrorKind::PermissionDenied))
+    })
+    .await
+    .unwrap_err();
+    assert!(matches!(error, OperationError::NonRetryableError(_)));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Exhausted retries keep the last error and the history
+    let error = retry(&config, || async {
+        Err::<(), _>(Error::from(ErrorKind::TimedOut))
+    })
+    .await
+    .unwrap_err();
+    assert_eq!(error.history().unwrap().attempts.len(), 4);
+    assert!(matches!(
+        error,
+        OperationError::RetriesExhausted { ref source, .. } if source.kind() == ErrorKind::TimedOut
+    ));
+}
+
+#[tokio::test]
+async fn test_body_and_decode_failures_are_retried() {
+    use std::collections::HashMap;
+
+    let config = || RetryConfig::new().base_delay(Duration::from_millis(1));
+
+    // The connection closes before the announced body length was sent
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 200 OK\r\ncontent-length: 20\r\n\r\n{\"id\":",
+        "HTTP/1.1 200 OK\r\ncontent-length: 8\r\n\r\n{\"id\":1}",
+    ])
+    .await;
+    let body = Client::new()
+        .get(&url)
+        .or_retry_bytes_with(config())
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"{\"id\":1}");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // A truncated JSON document is retried with the decode strategy
+    let reasons = Arc::new(Mutex::new(Vec::new()));
+    let recorded = reasons.clone();
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 200 OK\r\ncontent-length: 6\r\n\r\n{\"id\":",
+        "HTTP/1.1 200 OK\r\ncontent-length: 8\r\n\r\n{\"id\":1}",
+    ])
+    .await;
+    let value: HashMap<String, u32> = Client::new()
+        .get(&url)
+        .or_retry_json_with(config().on_retry(move |attempt| {
+            recorded.lock().unwrap().push(attempt.error_type.clone());
+        }))
+        .await
+        .unwrap();
+    assert_eq!(value["id"], 1);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(*reasons.lock().unwrap(), vec![RetryReason::DecodeError]);
+
+    // A complete document of the wrong shape is not retried
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\n{\"id\":\"a\"}",
+    ])
+    .await;
+    let error = Client::new()
+        .get(&url)
+        .or_retry_json_with::<HashMap<String, u32>>(config())
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::NonRetryableError(ref source) if source.is_decode()));
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // Decode failures follow their own error strategy
+    let (url, hits) = serve(vec!["HTTP/1.1 200 OK\r\ncontent-length: 6\r\n\r\n{\"id\":"]).await;
+    let error = Client::new()
+        .get(&url)
+        .or_retry_json_with::<HashMap<String, u32>>(config().error_strategy(
+            RetryReason::DecodeError,
+            ErrorStrategy::new().max_retries(1),
+        ))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::RequestError { .. }));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // A status still retryable when retries run out is reported with its history
+    let unavailable = "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n";
+    let (url, hits) = serve(vec![unavailable, unavailable]).await;
+    let error = Client::new()
+        .get(&url)
+        .or_retry_bytes_with(config().max_retries(1))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        RetryError::ResponseRetriesExhausted { ref response, ref history }
+            if response.status() == 503 && history.attempts.len() == 2
+    ));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_fine_grained_error_reasons() {
+    let reasons = |error: RetryError| -> Vec<RetryReason> {
+        let history = error.history().unwrap();
+        history.attempts.iter().map(|a| a.reason.clone()).collect()
+    };
+
+    // Timeouts are read timeouts, and fall back to the network error strategy
+    let error = Client::new()
+        .get(hanging_url().await)
+        .or_retry_with(
+            RetryConfig::new()
+                .max_retries(3)
+                .base_delay(Duration::from_millis(1))
+                .attempt_timeout(Duration::from_millis(20))
+                .error_strategy(
+                    RetryReason::NetworkError,
+                    ErrorStrategy::new().max_retries(1),
+                ),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(reasons(error), vec![RetryReason::ReadTimeout; 2]);
+
+    // Name resolution failures are found in the source chain
+    let error = Client::new()
+        .get("http://reqwest-retry.invalid/")
+        .or_retry_with(RetryConfig::new().max_retries(0))
+        .await
+        .unwrap_err();
+    assert_eq!(reasons(error), vec![RetryReason::Dns]);
+
+    // Other connect errors are plain network errors, not TLS failures
+    let error = Client::new()
+        .get(refused_url().await)
+        .or_retry_with(RetryConfig::new().max_retries(0))
+        .await
+        .unwrap_err();
+    assert_eq!(reasons(error), vec![RetryReason::NetworkError]);
+
+    // TLS handshake failures have no reliable shape and stay plain network errors
+    let (url, _) = serve(vec!["HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n"]).await;
+    let error = Client::new()
+        .get(url.replace("http:", "https:"))
+        .or_retry_with(RetryConfig::new().max_retries(0))
+        .await
+        .unwrap_err();
+    assert_eq!(reasons(error), vec![RetryReason::NetworkError]);
+
+    // Redirect loops are classified but not retried
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 302 Found\r\nlocation: /\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let error = Client::new()
+        .get(&url)
+        .or_retry_with(RetryConfig::new().on_failure(|attempt| {
+            assert_eq!(attempt.error_type, RetryReason::RedirectLoop);
+        }))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::NonRetryableError(ref source) if source.is_redirect()));
+    assert_eq!(hits.load(Ordering::SeqCst), 10);
+}
+
+#[tokio::test]
+async fn test_status_strategy_overrides_error_strategy() {
+    let config = || {
+        RetryConfig::new()
+            .max_retries(3)
+            .base_delay(Duration::from_millis(1))
+            .error_strategy(
+                RetryReason::ServerError,
+                ErrorStrategy::new().max_retries(2),
+            )
+            .status_strategy(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorStrategy::new().max_retries(0),
+            )
+    };
+
+    // 503 has its own strategy
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let response = Client::new()
+        .get(&url)
+        .or_retry_with(config())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // Other server errors keep the strategy of their error type
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 502 Bad Gateway\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let response = Client::new()
+        .get(&url)
+        .or_retry_with(config())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 502);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_policy_spec_converts_into_config() {
+    use crate::{PolicySpecError, RetryPolicySpec};
+
+    let spec: RetryPolicySpec = serde_json::from_str(
+        r#"{
+            "max_retries": 5,
+            "base_delay_ms": 50,
+            "max_delay_ms": 2000,
+            "backoff": "decorrelated_jitter",
+            "retryable_statuses": [502, 503],
+            "error_strategies": {
+                "rate_limit": { "max_retries": 8, "base_delay_ms": 1000 },
+                "custom:quota": { "max_retries": 2 }
+            },
+            "status_strategies": { "503": { "max_retries": 1 } }
+        }"#,
+    )
+    .unwrap();
+    let config = RetryConfig::try_from(spec).unwrap();
+    assert_eq!(config.max_retries, 5);
+    assert_eq!(config.base_delay, Duration::from_millis(50));
+    assert_eq!(config.max_delay, Duration::from_secs(2));
+
+    let rate_limit = config.get_status_strategy(&RetryReason::RateLimit, None);
+    assert_eq!(rate_limit.max_retries, 8);
+    assert_eq!(rate_limit.base_delay, Duration::from_secs(1));
+    let quota = config.get_status_strategy(&RetryReason::Custom("quota".to_string()), None);
+    assert_eq!(quota.max_retries, 2);
+    let unavailable = config.get_status_strategy(
+        &RetryReason::ServerError,
+        Some(StatusCode::SERVICE_UNAVAILABLE),
+    );
+    assert_eq!(unavailable.max_retries, 1);
+
+    // Unknown fields and backoff names are rejected while parsing
+    assert!(serde_json::from_str::<RetryPolicySpec>(r#"{ "max_retry": 1 }"#).is_err());
+    assert!(serde_json::from_str::<RetryPolicySpec>(r#"{ "backoff": "quadratic" }"#).is_err());
+
+    // Validation errors name the offending field
+    let invalid = |json: &str| {
+        let spec: RetryPolicySpec = serde_json::from_str(json).unwrap();
+        RetryConfig::try_from(spec).err().unwrap()
+    };
+    let field = |error: PolicySpecError| error.field;
+    assert_eq!(
+        field(invalid(r#"{ "base_delay_ms": 5000, "max_delay_ms": 100 }"#)),
+        "base_delay_ms"
+    );
+    assert_eq!(
+        field(invalid(r#"{ "retryable_statuses": [503, 42] }"#)),
+        "retryable_statuses[1]"
+    );
+    assert_eq!(
+        field(invalid(
+            r#"{ "error_strategies": { "dns": { "backoff_multiplier": 0.5 } } }"#
+        )),
+        "error_strategies.dns.backoff_multiplier"
+    );
+    // Reason names are not silently taken as custom reasons
+    assert_eq!(
+        field(invalid(r#"{ "error_strategies": { "rate_limited": {} } }"#)),
+        "error_strategies.rate_limited"
+    );
+    assert_eq!(
+        field(invalid(r#"{ "status_strategies": { "5xx": {} } }"#)),
+        "status_strategies.5xx"
+    );
+}
+
+#[tokio::test]
+async fn test_config_is_cloneable_debuggable_and_shareable() {
+    fn assert_shareable<T: Clone + std::fmt::Debug + Send + Sync>() {}
+    assert_shareable::<RetryConfig>();
+
+    // Hooks are shown by the name of their type, or by an explicit label
+    let mut config = RetryConfig::new()
+        .backoff(backoff::Linear)
+        .should_retry_error(predicates::network_errors_only)
+        .on_retry(|_| {});
+    config.on_retry = config.on_retry.map(|hook| hook.with_label("log_retry"));
+    let debug = format!("{:?}", config.clone());
+    assert!(debug.contains("backoff: reqwest_retry::backoff::Linear"));
+    assert!(debug.contains("should_retry: reqwest_retry::predicates::network_errors_only"));
+    assert!(debug.contains("on_retry: Some(log_retry)"));
+    assert!(debug.contains("on_failure: None"));
+    assert!(debug.contains("error_classifier: default"));
+    let strategy = ErrorStrategy::new().backoff(backoff::Fixed);
+    assert!(format!("{strategy:?}").contains("backoff: Some(reqwest_retry::backoff::Fixed)"));
+
+    // A shared config is borrowed by every request instead of being moved in
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let shared = Arc::new(RetryConfig::new().base_delay(Duration::from_millis(1)));
+    let client = Client::new();
+    for _ in 0..2 {
+        let response = client
+            .get(&url)
+            .or_retry_with(shared.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    assert_eq!(Arc::strong_count(&shared), 1);
+}
+
+#[test]
+fn test_registry_selects_config_by_route() {
+    use crate::{RetryPolicyRegistry, RouteMatcher};
+    use reqwest::{Method, Url};
+
+    let registry = RetryPolicyRegistry::new()
+        .route(
+            RouteMatcher::new()
+                .host("api.example.com")
+                .path_prefix("/v1/payments")
+                .method(Method::POST),
+            RetryConfig::new().max_retries(1),
+        )
+        .route(
+            RouteMatcher::new().host("*.example.com"),
+            RetryConfig::new().max_retries(5),
+        )
+        .default_config(RetryConfig::new().max_retries(2));
+    let max_retries = |method: Method, url: &str| {
+        registry
+            .lookup(&method, &Url::parse(url).unwrap())
+            .max_retries
+    };
+
+    assert_eq!(
+        max_retries(Method::POST, "https://api.example.com/v1/payments/42"),
+        1
+    );
+    assert_eq!(
+        max_retries(Method::POST, "https://API.example.com/v1/payments"),
+        1
+    );
+    // Routes match whole path segments and the listed methods only
+    assert_eq!(
+        max_retries(Method::POST, "https://api.example.com/v1/paymentsx"),
+        5
+    );
+    assert_eq!(
+        max_retries(Method::GET, "https://api.example.com/v1/payments"),
+        5
+    );
+    // Wildcards match subdomains but not the domain itself
+    assert_eq!(max_retries(Method::GET, "https://cdn.example.com/"), 5);
+    assert_eq!(max_retries(Method::GET, "https://example.com/"), 2);
+    assert_eq!(max_retries(Method::GET, "https://notexample.com/"), 2);
+}
+
+#[tokio::test]
+async fn test_or_retry_registry_uses_route_config() {
+    use crate::{RetryPolicyRegistry, RouteMatcher};
+
+    let (url, hits) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+    ])
+    .await;
+    let registry = RetryPolicyRegistry::new()
+        .route(
+            RouteMatcher::new().path_prefix("/reports"),
+            RetryConfig::new().max_retries(0),
+        )
+        .default_config(
+            RetryConfig::new()
+                .max_retries(2)
+                .base_delay(Duration::from_millis(1)),
+        );
+    let client = Client::new();
+
+    let response = client
+        .get(format!("{url}/reports/daily"))
+        .or_retry_registry(&registry)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    client
+        .get(format!("{url}/orders"))
+        .or_retry_registry(&registry)
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 4);
+}
@@ -0,0 +1,102 @@
+use crate::config::RetryConfig;
+use crate::drive::{drive, Attempt};
+use crate::tracker::RetryTracker;
+use reqwest::{Error as ReqwestError, Request, Response};
+use std::error::Error as StdError;
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Error type of a [`RetryService`], boxed like that of other tower middleware
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Tower layer wrapping a reqwest service with retries driven by a [`RetryConfig`]
+///
+/// Errors of the wrapped service that are a [`reqwest::Error`] are classified and
+/// retried; any other error, such as the `Elapsed` of a timeout layer, ends the request
+/// as is. Retry outcomes are returned as a boxed [`RetryError`](crate::RetryError).
+#[derive(Clone)]
+pub struct RetryLayer {
+    config: Arc<RetryConfig>,
+}
+
+impl RetryLayer {
+    /// Create a layer driven by the given configuration, owned or shared through an `Arc`
+    pub fn new(config: impl Into<Arc<RetryConfig>>) -> Self {
+        Self {
+            config: config.into(),
+        }
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = RetryService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RetryService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Tower service retrying requests sent through an inner reqwest service
+#[derive(Clone)]
+pub struct RetryService<S> {
+    inner: S,
+    config: Arc<RetryConfig>,
+}
+
+impl<S> Service<Request> for RetryService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+{
+    type Response = Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        // Keep the service that was driven to readiness and leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut ready = Some(std::mem::replace(&mut self.inner, clone));
+        let template = self.inner.clone();
+        let config = self.config.clone();
+
+        Box::pin(async move {
+            let tracker = RetryTracker::start(&config, Some(&mut req));
+
+            drive(tracker, &config, req, move |request| {
+                // Later attempts have to wait for a clone of the inner service
+                let waiting = ready.is_none();
+                let mut service = ready.take().unwrap_or_else(|| template.clone());
+                async move {
+                    if waiting && let Err(error) = poll_fn(|cx| service.poll_ready(cx)).await {
+                        return classify(error.into());
+                    }
+                    match service.call(request).await {
+                        Ok(response) => Ok(Attempt::Response(response)),
+                        Err(error) => classify(error.into()),
+                    }
+                }
+            })
+            .await
+        })
+    }
+}
+
+/// Retry a failed attempt only when its error is one of reqwest's
+fn classify(error: BoxError) -> Result<Attempt<Response>, BoxError> {
+    match error.downcast::<ReqwestError>() {
+        Ok(error) => Ok(Attempt::Error(*error)),
+        Err(error) => Err(error),
+    }
+}
@@ -0,0 +1,688 @@
+use crate::backoff::Backoff;
+use crate::config::RetryConfig;
+use crate::error::RetryError;
+use crate::history::{AttemptOutcome, RetryHistory};
+use crate::operation::{OperationError, Retryable};
+use crate::replay::BodySource;
+use crate::retry_metrics::RetryMetrics;
+use crate::spans::{AttemptSpan, RetrySpans};
+use crate::{response_retry_after, EffectiveStrategy, RetryAttempt, RetryReason};
+use reqwest::{Error as ReqwestError, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::time::{Duration, Instant, SystemTime};
+
+/// What to do after an attempt completed
+pub(crate) enum Decision {
+    /// Sleep for the given delay, then make another attempt
+    Retry(Duration),
+    /// Stop retrying and resolve with this result
+    Done(Result<Response, RetryError>),
+}
+
+/// Why no further attempt follows
+pub(crate) enum GiveUp {
+    /// The outcome is final, or retrying is not allowed
+    NonRetryable,
+    /// No retries remain for the outcome's reason
+    Exhausted,
+    /// The deadline passed or would pass while sleeping
+    DeadlineExceeded,
+    /// The circuit breaker rejected the attempt
+    CircuitOpen,
+    /// The shared retry budget has no tokens left
+    BudgetExhausted,
+}
+
+impl GiveUp {
+    /// Short name recorded as the span `outcome` field
+    fn outcome(&self) -> &'static str {
+        match self {
+            GiveUp::NonRetryable => "non_retryable",
+            GiveUp::Exhausted => "retries_exhausted",
+            GiveUp::DeadlineExceeded => "deadline_exceeded",
+            GiveUp::CircuitOpen => "circuit_open",
+            GiveUp::BudgetExhausted => "budget_exhausted",
+        }
+    }
+}
+
+/// Classified outcome of a failed or completed attempt, awaiting the retry decision
+struct Classified {
+    /// Reason the outcome was classified as
+    reason: RetryReason,
+    /// Response status whose own strategy applies
+    status: Option<StatusCode>,
+    /// Status of the response, if the attempt produced one
+    response_status: Option<u16>,
+    /// Error the attempt failed with, if it produced no response
+    error: Option<String>,
+    /// Delay requested by the server through `Retry-After`
+    retry_after: Option<Duration>,
+    /// Whether another attempt may succeed
+    retryable: bool,
+}
+
+impl Classified {
+    /// Outcome of an attempt that failed without a response
+    fn error(reason: RetryReason, retryable: bool, error: String) -> Self {
+        Self {
+            reason,
+            status: None,
+            response_status: None,
+            error: Some(error),
+            retry_after: None,
+            retryable,
+        }
+    }
+}
+
+/// Attempt set aside by [`RetryTracker::suspend_attempt`] while others are in flight
+pub(crate) struct PendingAttempt {
+    started: (SystemTime, Instant),
+    endpoint: Option<usize>,
+    span: AttemptSpan,
+}
+
+impl PendingAttempt {
+    /// Time since the attempt was sent
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.started.1.elapsed()
+    }
+}
+
+/// Short name for how a request ended, recorded as the span `outcome` field
+fn outcome(error: Option<&RetryError>) -> &'static str {
+    match error {
+        None => "completed",
+        Some(RetryError::NonRetryableError(_)) => "non_retryable",
+        Some(
+            RetryError::MaxRetriesExceeded { .. }
+            | RetryError::RequestError { .. }
+            | RetryError::ResponseRetriesExhausted { .. },
+        ) => "retries_exhausted",
+        Some(RetryError::DeadlineExceeded { .. }) => "deadline_exceeded",
+        Some(RetryError::CircuitOpen { .. }) => "circuit_open",
+        Some(RetryError::RetryCancelled { .. }) => "cancelled",
+        Some(RetryError::BudgetExhausted { .. } | RetryError::ResponseBudgetExhausted { .. }) => {
+            "budget_exhausted"
+        }
+        Some(_) => "error",
+    }
+}
+
+/// Per-request retry bookkeeping shared by every way of driving retries
+///
+/// The tracker owns no request and performs no I/O: drivers call [`next_request`]
+/// before sending, hand the outcome to [`on_response`] or [`on_error`], and act on the
+/// returned [`Decision`]. It keeps where the body of every attempt comes from.
+///
+/// [`next_request`]: RetryTracker::next_request
+/// [`on_response`]: RetryTracker::on_response
+/// [`on_error`]: RetryTracker::on_error
+pub(crate) struct RetryTracker {
+    retry_allowed: bool,
+    body: BodySource,
+    error_on_exhausted: bool,
+    attempts: usize,
+    current_error_type: Option<RetryReason>,
+    current_status: Option<StatusCode>,
+    backoffs: HashMap<(RetryReason, Option<StatusCode>), Box<dyn Backoff>>,
+    history: RetryHistory,
+    started: Option<Instant>,
+    attempt_started: (SystemTime, Instant),
+    last_retry: Option<RetryAttempt>,
+    endpoint: Option<usize>,
+    next_endpoint: Option<usize>,
+    spans: RetrySpans,
+    metrics: RetryMetrics,
+}
+
+impl RetryTracker {
+    /// Create a tracker for `request`; with `retry_allowed` unset every outcome is final
+    pub(crate) fn new(retry_allowed: bool, request: Option<&Request>) -> Self {
+        Self {
+            retry_allowed,
+            body: BodySource::Cloned,
+            error_on_exhausted: false,
+            attempts: 0,
+            current_error_type: None,
+            current_status: None,
+            backoffs: HashMap::new(),
+            history: RetryHistory::default(),
+            started: None,
+            attempt_started: (SystemTime::now(), Instant::now()),
+            last_retry: None,
+            endpoint: None,
+            next_endpoint: None,
+            spans: RetrySpans::new(request),
+            metrics: RetryMetrics::new(request),
+        }
+    }
+
+    /// Create a tracker for `request`, applying the preparation `config` makes once per request
+    ///
+    /// Without a request, e.g. because building it failed, every outcome is final.
+    pub(crate) fn start(config: &RetryConfig, request: Option<&mut Request>) -> Self {
+        let Some(request) = request else {
+            return Self::new(false, None);
+        };
+        config.prepare_request(request);
+
+        // Only retry requests whose method and headers make repeating them safe
+        let retry_allowed = config.is_retry_allowed(request.method(), request.headers());
+        let mut tracker = Self::new(retry_allowed, Some(request));
+
+        // Streaming bodies cannot be cloned and are buffered instead, if allowed
+        tracker.body = BodySource::detach(request, config);
+        tracker
+    }
+
+    /// Take the body of every attempt from `body` instead
+    pub(crate) fn set_body(&mut self, body: BodySource) {
+        self.body = body;
+    }
+
+    /// Whether copies of the request may be in flight at the same time
+    pub(crate) fn may_hedge(&self) -> bool {
+        // Copies of a buffered stream would read it concurrently
+        self.retry_allowed && !self.body.is_buffered()
+    }
+
+    /// Return exhausted retryable responses as errors, whatever the config says
+    pub(crate) fn error_on_exhausted_response(mut self) -> Self {
+        self.error_on_exhausted = true;
+        self
+    }
+
+    /// Start the next attempt, returning the copy of `template` to send
+    ///
+    /// The copy carries the attempt's body, timeout and endpoint.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn next_request(
+        &mut self,
+        config: &RetryConfig,
+        template: &Request,
+    ) -> Result<Request, RetryError> {
+        // Clone the request first, so a failed clone never holds a half-open probe slot
+        let mut request = self
+            .body
+            .attempt(template)
+            .ok_or(RetryError::RequestBuilderCloneError)?;
+
+        // Check retry limits and the deadline, and get the timeout for this attempt;
+        // a shorter timeout set on the request itself still applies
+        if let Some(timeout) = self.begin_attempt(config)? {
+            let current = request.timeout_mut();
+            *current = Some(current.map_or(timeout, |current| current.min(timeout)));
+        }
+        self.route(config, &mut request);
+        Ok(request)
+    }
+
+    /// Start the next attempt, returning the timeout it should be sent with
+    #[allow(clippy::result_large_err)]
+    fn begin_attempt(&mut self, config: &RetryConfig) -> Result<Option<Duration>, RetryError> {
+        self.start_attempt(config).map_err(|give_up| {
+            let history = self.history.finish(self.started);
+            match give_up {
+                GiveUp::DeadlineExceeded => RetryError::DeadlineExceeded { history },
+                GiveUp::CircuitOpen => RetryError::CircuitOpen { history },
+                _ => RetryError::MaxRetriesExceeded { history },
+            }
+        })
+    }
+
+    /// Start the next attempt of an arbitrary operation
+    pub(crate) fn begin_operation<E>(
+        &mut self,
+        config: &RetryConfig,
+    ) -> Result<(), OperationError<E>> {
+        self.start_attempt(config).map(|_| ()).map_err(|give_up| {
+            let history = self.history.finish(self.started);
+            match give_up {
+                GiveUp::DeadlineExceeded => OperationError::DeadlineExceeded { history },
+                GiveUp::CircuitOpen => OperationError::CircuitOpen { history },
+                _ => OperationError::MaxRetriesExceeded { history },
+            }
+        })
+    }
+
+    /// Check whether another attempt may start and open its instrumentation
+    fn start_attempt(&mut self, config: &RetryConfig) -> Result<Option<Duration>, GiveUp> {
+        let result = self.check_attempt(config);
+        match &result {
+            Ok(_) => {
+                self.spans.begin_attempt();
+                self.metrics.attempt_started();
+            }
+            Err(give_up) => self.spans.finish(give_up.outcome()),
+        }
+        self.observe_circuit(config);
+        result
+    }
+
+    /// Send the attempt to the endpoint chosen by the failover, if any
+    fn route(&mut self, config: &RetryConfig, request: &mut Request) {
+        if let Some(failover) = &config.failover {
+            self.endpoint = self.next_endpoint.take().or_else(|| failover.select());
+            if let Some(index) = self.endpoint {
+                failover.apply(index, request.url_mut());
+            }
+        }
+    }
+
+    /// Set the current attempt aside, so another one can start while it is in flight
+    pub(crate) fn suspend_attempt(&mut self) -> PendingAttempt {
+        PendingAttempt {
+            started: self.attempt_started,
+            endpoint: self.endpoint,
+            span: self.spans.take_attempt(),
+        }
+    }
+
+    /// Make a suspended attempt current again, before handing over its outcome
+    pub(crate) fn resume_attempt(&mut self, attempt: PendingAttempt) {
+        self.attempt_started = attempt.started;
+        self.endpoint = attempt.endpoint;
+        self.spans.resume_attempt(attempt.span);
+    }
+
+    /// Details of the retry that was decided last
+    pub(crate) fn last_retry(&self) -> Option<&RetryAttempt> {
+        self.last_retry.as_ref()
+    }
+
+    /// Stop retrying because the `before_retry` hook declined to proceed
+    pub(crate) fn cancel(&mut self) -> RetryError {
+        let error = RetryError::RetryCancelled {
+            history: self.history.finish(self.started),
+        };
+        self.spans.finish(outcome(Some(&error)));
+        error
+    }
+
+    /// Run the future of the current attempt inside its span
+    pub(crate) fn instrument<F: Future>(
+        &self,
+        future: F,
+    ) -> impl Future<Output = F::Output> + use<F> {
+        self.spans.instrument(future)
+    }
+
+    /// Decide what to do with a response received for the current attempt
+    pub(crate) fn on_response(&mut self, config: &RetryConfig, response: Response) -> Decision {
+        let decision = self.decide_response(config, response);
+        self.end_attempt(config, &decision);
+        decision
+    }
+
+    /// Decide what to do with an error produced by the current attempt
+    pub(crate) fn on_error(&mut self, config: &RetryConfig, error: ReqwestError) -> Decision {
+        let decision = self.decide_error(config, error);
+        self.end_attempt(config, &decision);
+        decision
+    }
+
+    /// Record that the current attempt succeeded without a response left to classify
+    pub(crate) fn on_success(&mut self, config: &RetryConfig) {
+        if let Some(breaker) = &config.circuit_breaker {
+            breaker.record(false);
+        }
+        self.record_endpoint(config, false);
+        if let Some(budget) = &config.retry_budget {
+            budget.deposit();
+        }
+        self.finish_attempt(config, None, outcome(None));
+    }
+
+    /// Decide whether to retry after an arbitrary operation failed with `error`
+    pub(crate) fn on_failure<E: Retryable + Display>(
+        &mut self,
+        config: &RetryConfig,
+        error: E,
+    ) -> Result<Duration, OperationError<E>> {
+        let error_type = error.retry_reason();
+        self.spans.record_reason(&error_type, None);
+        self.history.push(
+            AttemptOutcome::Error(error.to_string()),
+            error_type.clone(),
+            self.attempt_started,
+        );
+
+        let outcome = Classified::error(error_type, error.is_retryable(), error.to_string());
+        let decision = self.decide(config, outcome);
+        let outcome = decision.as_ref().err().map_or("retry", GiveUp::outcome);
+        self.finish_attempt(config, decision.as_ref().ok().copied(), outcome);
+
+        decision.map_err(|give_up| {
+            let history = self.history.finish(self.started);
+            match give_up {
+                GiveUp::NonRetryable => OperationError::NonRetryableError(error),
+                GiveUp::BudgetExhausted => OperationError::BudgetExhausted {
+                    source: error,
+                    history,
+                },
+                GiveUp::DeadlineExceeded => OperationError::DeadlineExceeded { history },
+                GiveUp::CircuitOpen => OperationError::CircuitOpen { history },
+                GiveUp::Exhausted => OperationError::RetriesExhausted {
+                    source: error,
+                    history,
+                },
+            }
+        })
+    }
+
+    /// Close the instrumentation of the attempt that just completed
+    fn end_attempt(&mut self, config: &RetryConfig, decision: &Decision) {
+        match decision {
+            Decision::Retry(delay) => self.finish_attempt(config, Some(*delay), "retry"),
+            Decision::Done(result) => {
+                self.finish_attempt(config, None, outcome(result.as_ref().err()))
+            }
+        }
+    }
+
+    /// Record the duration and outcome of the attempt that just completed
+    fn finish_attempt(
+        &mut self,
+        config: &RetryConfig,
+        retry: Option<Duration>,
+        outcome: &'static str,
+    ) {
+        self.metrics
+            .attempt_finished(self.attempt_started.1.elapsed());
+        self.observe_circuit(config);
+        self.spans.end_attempt(retry, outcome);
+    }
+
+    /// Report the state of the circuit breaker, if any
+    fn observe_circuit(&self, config: &RetryConfig) {
+        if let Some(breaker) = &config.circuit_breaker {
+            self.metrics.circuit_state(breaker.state());
+        }
+    }
+
+    /// Check retry limits, the deadline and the circuit breaker before an attempt
+    /// Strategy for the current error type, or the default one before any failure
+    fn current_strategy(&self, config: &RetryConfig) -> EffectiveStrategy {
+        if let Some(error_type) = self.current_error_type.as_ref() {
+            config.get_status_strategy(error_type, self.current_status)
+        } else {
+            // First attempt, use default strategy
+            EffectiveStrategy {
+                max_retries: config.max_retries,
+                base_delay: config.base_delay,
+                max_delay: config.max_delay,
+                backoff_multiplier: config.backoff_multiplier,
+                backoff: config.backoff.clone(),
+            }
+        }
+    }
+
+    /// Count a copy sent before the previous attempt finished as one of the retries
+    ///
+    /// Returns false, counting nothing, once no retry is left.
+    pub(crate) fn reserve_hedge(&mut self, config: &RetryConfig) -> bool {
+        if self.attempts >= self.current_strategy(config).max_retries {
+            return false;
+        }
+        self.attempts += 1;
+        true
+    }
+
+    fn check_attempt(&mut self, config: &RetryConfig) -> Result<Option<Duration>, GiveUp> {
+        // Get effective strategy for current error type
+        let strategy = self.current_strategy(config);
+
+        // Check if we've exceeded max retries for this error type
+        if self.attempts > strategy.max_retries {
+            return Err(GiveUp::Exhausted);
+        }
+
+        // Start the clocks for the operation and this attempt
+        let started = *self.started.get_or_insert_with(Instant::now);
+        self.attempt_started = (SystemTime::now(), Instant::now());
+
+        // Check if the overall deadline has already passed
+        let remaining = config
+            .deadline
+            .map(|deadline| deadline.saturating_sub(started.elapsed()));
+        if remaining == Some(Duration::ZERO) {
+            return Err(GiveUp::DeadlineExceeded);
+        }
+
+        // Fail fast while the shared circuit breaker is open
+        if let Some(breaker) = &config.circuit_breaker
+            && !breaker.try_acquire()
+        {
+            return Err(GiveUp::CircuitOpen);
+        }
+
+        // Bound the attempt by its own timeout and the time left before the deadline
+        Ok([config.attempt_timeout, remaining]
+            .into_iter()
+            .flatten()
+            .min())
+    }
+
+    fn decide_response(&mut self, config: &RetryConfig, response: Response) -> Decision {
+        // Classify the response error
+        let error_type = (config.response_classifier)(&response);
+        self.spans
+            .record_reason(&error_type, Some(response.status().as_u16()));
+        self.history.push(
+            AttemptOutcome::Status(response.status().as_u16()),
+            error_type.clone(),
+            self.attempt_started,
+        );
+
+        // A strategy set for the exact status takes precedence over the error type's
+        let status =
+            Some(response.status()).filter(|status| config.status_strategies.contains_key(status));
+        let retry_after = if config.respect_retry_after {
+            response_retry_after(&response)
+        } else {
+            None
+        };
+
+        // Check if response indicates we should retry
+        let outcome = Classified {
+            reason: error_type,
+            status,
+            response_status: Some(response.status().as_u16()),
+            error: None,
+            retry_after,
+            retryable: (config.should_retry_response)(&response),
+        };
+        match self.decide(config, outcome) {
+            Ok(delay) => Decision::Retry(delay),
+            Err(GiveUp::NonRetryable) => Decision::Done(Ok(response)),
+            Err(GiveUp::BudgetExhausted) => {
+                let history = self.history.finish(self.started);
+                Decision::Done(Err(RetryError::ResponseBudgetExhausted {
+                    response,
+                    history,
+                }))
+            }
+            Err(GiveUp::DeadlineExceeded) => {
+                let history = self.history.finish(self.started);
+                Decision::Done(Err(RetryError::DeadlineExceeded { history }))
+            }
+            Err(_) if self.error_on_exhausted || config.error_on_exhausted_response => {
+                let history = self.history.finish(self.started);
+                Decision::Done(Err(RetryError::ResponseRetriesExhausted {
+                    response,
+                    history,
+                }))
+            }
+            Err(_) => Decision::Done(Ok(response)),
+        }
+    }
+
+    fn decide_error(&mut self, config: &RetryConfig, error: ReqwestError) -> Decision {
+        // Classify the error
+        let error_type = (config.error_classifier)(&error);
+        self.spans
+            .record_reason(&error_type, error.status().map(|status| status.as_u16()));
+        self.history.push(
+            AttemptOutcome::Error(error.to_string()),
+            error_type.clone(),
+            self.attempt_started,
+        );
+
+        // Check if this error should trigger a retry
+        let retryable = (config.should_retry)(&error);
+        let outcome = Classified::error(error_type, retryable, error.to_string());
+        match self.decide(config, outcome) {
+            Ok(delay) => Decision::Retry(delay),
+            Err(GiveUp::NonRetryable) => Decision::Done(Err(RetryError::NonRetryableError(error))),
+            Err(give_up) => {
+                let history = self.history.finish(self.started);
+                Decision::Done(Err(match give_up {
+                    GiveUp::BudgetExhausted => RetryError::BudgetExhausted {
+                        source: error,
+                        history,
+                    },
+                    GiveUp::DeadlineExceeded => RetryError::DeadlineExceeded { history },
+                    _ => RetryError::RequestError {
+                        source: error,
+                        history,
+                    },
+                }))
+            }
+        }
+    }
+
+    /// Decide whether a classified attempt is retried, and after which delay
+    fn decide(&mut self, config: &RetryConfig, outcome: Classified) -> Result<Duration, GiveUp> {
+        // A retryable outcome counts as a failure of the circuit and the endpoint,
+        // any other response as a success that refills the retry budget
+        if let Some(breaker) = &config.circuit_breaker {
+            breaker.record(outcome.retryable);
+        }
+        self.record_endpoint(config, outcome.retryable);
+        if !outcome.retryable
+            && outcome.response_status.is_some()
+            && let Some(budget) = &config.retry_budget
+        {
+            budget.deposit();
+        }
+        if !self.retry_allowed || !outcome.retryable {
+            return Err(GiveUp::NonRetryable);
+        }
+
+        let strategy = config.get_status_strategy(&outcome.reason, outcome.status);
+
+        // Honor the server's Retry-After hint; retrying earlier than asked is not an option,
+        // so a hint beyond the longest accepted wait ends retrying
+        let wait_too_long = outcome
+            .retry_after
+            .is_some_and(|hint| hint > config.max_retry_after);
+
+        // Otherwise calculate delay using error-specific strategy while retries remain and
+        // the body can be sent again, then check that the deadline allows sleeping and the
+        // budget allows the retry
+        let replayable = self.body.can_replay();
+        let delay =
+            (self.attempts < strategy.max_retries && !wait_too_long && replayable).then(|| {
+                outcome
+                    .retry_after
+                    .unwrap_or_else(|| self.next_delay(&outcome.reason, outcome.status, &strategy))
+            });
+        let within_deadline =
+            delay.is_some_and(|delay| !config.exceeds_deadline(self.started, delay));
+        let budget_exhausted = within_deadline && !config.try_withdraw_retry();
+
+        let retry = match delay {
+            Some(delay) if within_deadline && !budget_exhausted => {
+                self.attempts += 1;
+                self.current_error_type = Some(outcome.reason.clone());
+                self.current_status = outcome.status;
+                self.metrics.retry(&outcome.reason, delay);
+                Some(delay)
+            }
+            _ => {
+                self.metrics.exhausted();
+                None
+            }
+        };
+
+        let info = RetryAttempt {
+            attempt: self.attempts,
+            max_attempts: strategy.max_retries + 1,
+            delay: retry.unwrap_or_default(),
+            error: outcome.error,
+            response_status: outcome.response_status,
+            error_type: outcome.reason,
+            retry_after: outcome.retry_after,
+            budget_exhausted,
+            endpoint: None,
+        };
+        match retry {
+            Some(delay) => {
+                // Call retry callback if provided
+                let info = RetryAttempt {
+                    endpoint: self.select_endpoint(config),
+                    ..info
+                };
+                if let Some(on_retry) = &config.on_retry {
+                    on_retry(&info);
+                }
+                self.last_retry = Some(info);
+
+                self.history.set_last_delay(delay);
+                Ok(delay)
+            }
+            None => {
+                // Call failure callback if provided
+                if let Some(on_failure) = &config.on_failure {
+                    on_failure(&info);
+                }
+
+                // A pending delay means retries remained but the budget or deadline did not
+                if budget_exhausted {
+                    Err(GiveUp::BudgetExhausted)
+                } else if delay.is_some() {
+                    Err(GiveUp::DeadlineExceeded)
+                } else {
+                    Err(GiveUp::Exhausted)
+                }
+            }
+        }
+    }
+
+    /// Update the health of the endpoint the current attempt was sent to
+    fn record_endpoint(&self, config: &RetryConfig, failure: bool) {
+        if let (Some(failover), Some(index)) = (&config.failover, self.endpoint) {
+            failover.record(index, failure);
+        }
+    }
+
+    /// Choose the endpoint of the next attempt, returning its base URL
+    fn select_endpoint(&mut self, config: &RetryConfig) -> Option<String> {
+        let failover = config.failover.as_ref()?;
+        self.next_endpoint = failover.select();
+        self.next_endpoint
+            .map(|index| failover.endpoint(index).to_string())
+    }
+
+    /// Advance the backoff state kept for `error_type`, or its status strategy, to the next
+    /// attempt
+    fn next_delay(
+        &mut self,
+        error_type: &RetryReason,
+        status: Option<StatusCode>,
+        strategy: &EffectiveStrategy,
+    ) -> Duration {
+        self.backoffs
+            .entry((error_type.clone(), status))
+            .or_insert_with(|| (strategy.backoff)())
+            .next_delay(
+                self.attempts + 1,
+                strategy.base_delay,
+                strategy.backoff_multiplier,
+                strategy.max_delay,
+            )
+    }
+}
@@ -1,21 +1,89 @@
 use crate::config::RetryConfig;
+use crate::decode::DecodeFuture;
+use crate::hedge::{HedgeConfig, HedgeFuture};
+use crate::registry::RetryPolicyRegistry;
 use crate::retry_future::RetryFuture;
+use bytes::Bytes;
+use reqwest::Response;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
 
 /// Extension trait for reqwest::RequestBuilder to add retry functionality
 pub trait RetryExt {
     /// Add retry functionality with default configuration
     fn or_retry(self) -> RetryFuture;
 
-    /// Add retry functionality with custom configuration
-    fn or_retry_with(self, config: RetryConfig) -> RetryFuture;
+    /// Add retry functionality with custom configuration, owned or shared through an `Arc`
+    fn or_retry_with(self, config: impl Into<Arc<RetryConfig>>) -> RetryFuture;
+
+    /// Add retry functionality with the config `registry` selects for the request
+    fn or_retry_registry(self, registry: &RetryPolicyRegistry) -> RetryFuture;
+
+    /// Retry until the full response body was read, with default configuration
+    fn or_retry_bytes(self) -> DecodeFuture<Bytes>;
+
+    /// Retry until the full response body was read, with custom configuration
+    fn or_retry_bytes_with(self, config: impl Into<Arc<RetryConfig>>) -> DecodeFuture<Bytes>;
+
+    /// Retry until the response body was decoded from JSON, with default configuration
+    fn or_retry_json<T: DeserializeOwned + Send + 'static>(self) -> DecodeFuture<T>;
+
+    /// Retry until the response body was decoded from JSON, with custom configuration
+    fn or_retry_json_with<T: DeserializeOwned + Send + 'static>(
+        self,
+        config: impl Into<Arc<RetryConfig>>,
+    ) -> DecodeFuture<T>;
+
+    /// Send hedged copies of the request with default retry rules
+    fn or_hedge(self, hedge: HedgeConfig) -> HedgeFuture;
+
+    /// Send hedged copies of the request, bounded by the idempotency rules and
+    /// `max_retries` of `config`
+    fn or_hedge_with(self, config: impl Into<Arc<RetryConfig>>, hedge: HedgeConfig) -> HedgeFuture;
 }
 
 impl RetryExt for reqwest::RequestBuilder {
     fn or_retry(self) -> RetryFuture {
-        RetryFuture::new(self, RetryConfig::default())
+        self.or_retry_with(RetryConfig::default())
+    }
+
+    fn or_retry_with(self, config: impl Into<Arc<RetryConfig>>) -> RetryFuture {
+        RetryFuture::new(self, config.into())
+    }
+
+    fn or_retry_registry(self, registry: &RetryPolicyRegistry) -> RetryFuture {
+        let (client, request) = self.build_split();
+        let config = match &request {
+            Ok(request) => registry.lookup(request.method(), request.url()),
+            Err(_) => registry.fallback(),
+        };
+        RetryFuture::from_parts(client, request, config)
+    }
+
+    fn or_retry_bytes(self) -> DecodeFuture<Bytes> {
+        self.or_retry_bytes_with(RetryConfig::default())
+    }
+
+    fn or_retry_bytes_with(self, config: impl Into<Arc<RetryConfig>>) -> DecodeFuture<Bytes> {
+        DecodeFuture::new(self, config.into(), Response::bytes)
+    }
+
+    fn or_retry_json<T: DeserializeOwned + Send + 'static>(self) -> DecodeFuture<T> {
+        self.or_retry_json_with(RetryConfig::default())
+    }
+
+    fn or_retry_json_with<T: DeserializeOwned + Send + 'static>(
+        self,
+        config: impl Into<Arc<RetryConfig>>,
+    ) -> DecodeFuture<T> {
+        DecodeFuture::new(self, config.into(), Response::json::<T>)
+    }
+
+    fn or_hedge(self, hedge: HedgeConfig) -> HedgeFuture {
+        self.or_hedge_with(RetryConfig::default(), hedge)
     }
 
-    fn or_retry_with(self, config: RetryConfig) -> RetryFuture {
-        RetryFuture::new(self, config)
+    fn or_hedge_with(self, config: impl Into<Arc<RetryConfig>>, hedge: HedgeConfig) -> HedgeFuture {
+        HedgeFuture::new(self, config.into(), hedge)
     }
 }